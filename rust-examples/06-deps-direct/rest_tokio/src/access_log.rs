@@ -0,0 +1,472 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::config::AccessLogFormat;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Metadata, Span, Subscriber, info, info_span};
+use tracing_subscriber::Layer;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use warp::http::header::{CONTENT_LENGTH, HeaderMap, HeaderValue};
+use warp::http::{Method, StatusCode};
+use warp::hyper::body::HttpBody;
+use warp::path::FullPath;
+use warp::{Filter, Reply};
+
+/// Tracing target of the access log events.
+pub(crate) const TARGET: &str = "access_log";
+/// Header that carries the request id from the caller and back.
+pub(crate) const REQUEST_ID: &str = "x-request-id";
+/// Longest request id accepted from a caller.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Identifies a request in the logs of this service and of its callers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RequestId(String);
+
+impl RequestId {
+    /// Takes the id the caller sent if it is safe to log and echo, otherwise
+    /// generates a new one.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid(id))
+            .map(|id| Self(id.to_string()))
+            .unwrap_or_else(Self::generate)
+    }
+
+    fn generate() -> Self {
+        Self(format!("{:032x}", rand::random::<u128>()))
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// What the access log needs to know about a request before it is handled.
+#[derive(Debug)]
+pub(crate) struct Request {
+    id: RequestId,
+    started: Instant,
+    method: Method,
+    target: String,
+    remote_addr: Option<SocketAddr>,
+}
+
+/// Starts the bookkeeping of a request and records its id in the request span.
+pub(crate) fn request() -> impl Filter<Extract = (Request,), Error = Infallible> + Clone {
+    let query = warp::query::raw().or(warp::any().map(String::new)).unify();
+
+    warp::any()
+        .map(Instant::now)
+        .and(warp::header::headers_cloned())
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(query)
+        .and(warp::addr::remote())
+        .map(
+            |started, headers: HeaderMap, method, path: FullPath, query: String, remote_addr| {
+                let id = RequestId::from_headers(&headers);
+                Span::current().record("request_id", id.as_str());
+                let target = if query.is_empty() {
+                    path.as_str().to_string()
+                } else {
+                    format!("{}?{}", path.as_str(), query)
+                };
+                Request {
+                    id,
+                    started,
+                    method,
+                    target,
+                    remote_addr,
+                }
+            },
+        )
+}
+
+/// Span of a request. It carries the id of the caller right away, a
+/// generated id is recorded by [`request`].
+pub(crate) fn span(info: warp::trace::Info) -> Span {
+    let remote_addr = info
+        .remote_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let request_id = info
+        .request_headers()
+        .get(REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid(id));
+    info_span!(
+        "request",
+        method = %info.method(),
+        path = info.path(),
+        version = ?info.version(),
+        remote_addr,
+        request_id,
+    )
+}
+
+/// Logs one event per request to the [`TARGET`] of the access log.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+}
+
+impl AccessLog {
+    pub(crate) fn new(format: AccessLogFormat) -> Self {
+        Self { format }
+    }
+
+    /// Echoes the request id in the response and logs the request.
+    pub(crate) fn finish<R: Reply>(&self, request: Request, reply: R) -> warp::reply::Response {
+        let mut response = reply.into_response();
+        if let Ok(id) = HeaderValue::from_str(request.id.as_str()) {
+            response.headers_mut().insert(REQUEST_ID, id);
+        }
+        if self.format != AccessLogFormat::Off {
+            log(&request, &response);
+        }
+        response
+    }
+}
+
+fn log(request: &Request, response: &warp::reply::Response) {
+    let remote_addr = request.remote_addr.map(|addr| addr.to_string());
+    info!(
+        target: TARGET,
+        request_id = request.id.as_str(),
+        remote_addr = remote_addr.as_deref(),
+        method = request.method.as_str(),
+        request_target = request.target.as_str(),
+        status = response.status().as_u16(),
+        bytes = body_len(response),
+        latency_us = request.started.elapsed().as_micros() as u64,
+    );
+}
+
+/// Writes the access log events, one line each, independent of the log
+/// filter. The protocol is taken from the request span.
+pub(crate) struct AccessLogLayer<W> {
+    format: AccessLogFormat,
+    writer: W,
+}
+
+impl<W> AccessLogLayer<W> {
+    pub(crate) fn new(format: AccessLogFormat, writer: W) -> Self {
+        Self { format, writer }
+    }
+}
+
+/// Filter of the [`AccessLogLayer`]: the access log events and the request
+/// spans they are logged in.
+pub(crate) fn is_access_log(metadata: &Metadata<'_>) -> bool {
+    metadata.target() == TARGET || (metadata.is_span() && metadata.name() == "request")
+}
+
+/// HTTP version of a request, kept with its span.
+struct Protocol(String);
+
+impl Visit for Protocol {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "version" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl<S, W> Layer<S> for AccessLogLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut protocol = Protocol(String::new());
+        attrs.record(&mut protocol);
+        if let Some(span) = ctx.span(id).filter(|_| !protocol.0.is_empty()) {
+            span.extensions_mut().insert(protocol);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != TARGET {
+            return;
+        }
+        let mut entry = Entry {
+            timestamp: Utc::now(),
+            ..Entry::default()
+        };
+        event.record(&mut entry);
+        entry.protocol = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .filter_map(|span| span.extensions().get::<Protocol>().map(|p| p.0.clone()))
+                .next()
+        });
+
+        let mut line = match self.format {
+            AccessLogFormat::Common => common(&entry),
+            AccessLogFormat::Json => json(&entry),
+            AccessLogFormat::Off => return,
+        };
+        line.push('\n');
+        // One write per line, so that lines of concurrent requests do not mix.
+        // A full or closed output must not fail the request.
+        let _ = self.writer.make_writer().write_all(line.as_bytes());
+    }
+}
+
+/// One access log line, collected from the fields of an access log event.
+#[derive(Debug, Default, Serialize)]
+struct Entry {
+    timestamp: DateTime<Utc>,
+    request_id: String,
+    remote_addr: Option<SocketAddr>,
+    method: String,
+    target: String,
+    protocol: Option<String>,
+    status: u16,
+    bytes: Option<u64>,
+    latency_us: u64,
+}
+
+impl Visit for Entry {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "request_id" => self.request_id = value.to_string(),
+            "remote_addr" => self.remote_addr = value.parse().ok(),
+            "method" => self.method = value.to_string(),
+            "request_target" => self.target = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "status" => self.status = u16::try_from(value).unwrap_or_default(),
+            "bytes" => self.bytes = Some(value),
+            "latency_us" => self.latency_us = value,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Length of the response body, if it is known before it is sent.
+fn body_len(response: &warp::reply::Response) -> Option<u64> {
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Some(0);
+    }
+    response.body().size_hint().exact().or_else(|| {
+        response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+    })
+}
+
+/// Common log format, followed by the request id and the latency in microseconds:
+/// `127.0.0.1 - - [01/May/2024:01:00:00 +0000] "GET /stats HTTP/1.1" 200 412 4f0c... 153`
+fn common(entry: &Entry) -> String {
+    let remote_addr = entry
+        .remote_addr
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| String::from("-"));
+    let bytes = entry
+        .bytes
+        .map(|bytes| bytes.to_string())
+        .unwrap_or_else(|| String::from("-"));
+    let protocol = entry
+        .protocol
+        .as_deref()
+        .map(|protocol| format!(" {}", protocol))
+        .unwrap_or_default();
+    format!(
+        "{} - - [{}] \"{} {}{}\" {} {} {} {}",
+        remote_addr,
+        entry.timestamp.format("%d/%b/%Y:%H:%M:%S %z"),
+        entry.method,
+        entry.target,
+        protocol,
+        entry.status,
+        bytes,
+        entry.request_id,
+        entry.latency_us,
+    )
+}
+
+fn json(entry: &Entry) -> String {
+    serde_json::to_string(entry).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::filter::filter_fn;
+    use tracing_subscriber::layer::SubscriberExt;
+    use warp::http::Version;
+
+    fn headers(id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_ID, HeaderValue::from_str(id).unwrap());
+        headers
+    }
+
+    fn entry(request: &Request, bytes: Option<u64>) -> Entry {
+        Entry {
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 1, 0, 0).unwrap(),
+            request_id: request.id.as_str().to_string(),
+            remote_addr: request.remote_addr,
+            method: request.method.as_str().to_string(),
+            target: request.target.clone(),
+            protocol: Some(String::from("HTTP/1.1")),
+            status: 200,
+            bytes,
+            latency_us: 153,
+        }
+    }
+
+    /// Collects what an access log layer writes.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn lines(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn sample_request() -> Request {
+        Request {
+            id: RequestId(String::from("abc-123")),
+            started: Instant::now(),
+            method: Method::GET,
+            target: String::from("/assets?limit=1"),
+            remote_addr: Some(SocketAddr::from(([10, 0, 0, 7], 51234))),
+        }
+    }
+
+    #[test]
+    fn request_id_is_taken_from_the_caller() {
+        assert_eq!(
+            RequestId::from_headers(&headers("abc-123")).as_str(),
+            "abc-123"
+        );
+    }
+
+    #[test]
+    fn request_id_is_generated_if_missing_or_invalid() {
+        let generated = RequestId::from_headers(&HeaderMap::new());
+        assert_eq!(generated.as_str().len(), 32);
+        assert_ne!(generated, RequestId::from_headers(&HeaderMap::new()));
+
+        let spaced = RequestId::from_headers(&headers("a b"));
+        assert_ne!(spaced.as_str(), "a b");
+        let long = "x".repeat(MAX_REQUEST_ID_LEN + 1);
+        assert_ne!(RequestId::from_headers(&headers(&long)).as_str(), long);
+    }
+
+    #[test]
+    fn common_line() {
+        let request = sample_request();
+        assert_eq!(
+            common(&entry(&request, Some(412))),
+            r#"10.0.0.7 - - [01/May/2024:01:00:00 +0000] "GET /assets?limit=1 HTTP/1.1" 200 412 abc-123 153"#
+        );
+        assert!(common(&entry(&request, None)).contains(" 200 - abc-123 "));
+    }
+
+    #[test]
+    fn json_line() {
+        let request = sample_request();
+        let line: serde_json::Value =
+            serde_json::from_str(&json(&entry(&request, Some(412)))).unwrap();
+
+        assert_eq!(line["request_id"], "abc-123");
+        assert_eq!(line["remote_addr"], "10.0.0.7:51234");
+        assert_eq!(line["target"], "/assets?limit=1");
+        assert_eq!(line["protocol"], "HTTP/1.1");
+        assert_eq!(line["bytes"], 412);
+        assert_eq!(line["latency_us"], 153);
+    }
+
+    #[test]
+    fn layer_writes_the_events_with_the_protocol_of_the_request() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            AccessLogLayer::new(AccessLogFormat::Common, move || writer.clone())
+                .with_filter(filter_fn(is_access_log)),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _request = info_span!("request", version = ?Version::HTTP_2).entered();
+            info!("not an access log event");
+            let response = warp::reply::Response::new("ok".into());
+            log(&sample_request(), &response);
+        });
+
+        let lines = output.lines();
+        assert_eq!(lines.lines().count(), 1, "{}", lines);
+        assert!(lines.starts_with("10.0.0.7 - - ["), "{}", lines);
+        assert!(
+            lines.contains(r#""GET /assets?limit=1 HTTP/2.0" 200 2 abc-123 "#),
+            "{}",
+            lines
+        );
+    }
+
+    #[tokio::test]
+    async fn response_echoes_the_request_id() {
+        let access_log = AccessLog::new(AccessLogFormat::Off);
+        let filter = request()
+            .and(warp::any().map(|| "ok"))
+            .map(move |request, reply| access_log.finish(request, reply));
+
+        let echoed = warp::test::request()
+            .header(REQUEST_ID, "abc-123")
+            .reply(&filter)
+            .await;
+        let generated = warp::test::request().reply(&filter).await;
+
+        assert_eq!(echoed.headers()[REQUEST_ID], "abc-123");
+        assert_eq!(generated.headers()[REQUEST_ID].len(), 32);
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::sync::Arc;
+use warp::Filter;
+use warp::reject::Reject;
+
+/// Rejection for requests without a valid bearer token.
+#[derive(Debug)]
+pub(crate) struct Unauthorized;
+
+impl Reject for Unauthorized {}
+
+/// Accepts requests with an `Authorization: Bearer <token>` header that matches `token`.
+///
+/// Without a configured token every request is rejected, which disables the
+/// routes behind this filter.
+pub(crate) fn bearer_token(
+    token: Option<String>,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    let token: Option<Arc<str>> = token.map(Into::into);
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let token = token.clone();
+            async move {
+                let presented = header.as_deref().and_then(|h| h.strip_prefix("Bearer "));
+                match (token.as_deref(), presented) {
+                    (Some(expected), Some(presented)) if constant_time_eq(expected, presented) => {
+                        Ok(())
+                    }
+                    _ => Err(warp::reject::custom(Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Compares two strings without short-circuiting on the first differing byte.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bearer_token_checks_header() {
+        let filter = bearer_token(Some(String::from("s3cret")));
+
+        let valid = warp::test::request()
+            .header("authorization", "Bearer s3cret")
+            .filter(&filter)
+            .await;
+        let wrong = warp::test::request()
+            .header("authorization", "Bearer s3cre7")
+            .filter(&filter)
+            .await;
+        let missing = warp::test::request().filter(&filter).await;
+
+        assert!(valid.is_ok());
+        assert!(wrong.unwrap_err().find::<Unauthorized>().is_some());
+        assert!(missing.unwrap_err().find::<Unauthorized>().is_some());
+    }
+
+    #[tokio::test]
+    async fn missing_token_disables_routes() {
+        let filter = bearer_token(None);
+
+        let result = warp::test::request()
+            .header("authorization", "Bearer ")
+            .filter(&filter)
+            .await;
+
+        assert!(result.unwrap_err().find::<Unauthorized>().is_some());
+    }
+}
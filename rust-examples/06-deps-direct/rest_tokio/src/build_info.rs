@@ -0,0 +1,99 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Version and build details, embedded at compile time.
+//!
+//! Bazel sets the variables below from `build_info.env`, stamped with the
+//! workspace status of `build/workspace_status.sh` when building with
+//! `--stamp`. Unstamped builds and Cargo builds report `unknown` instead.
+
+use crate::types::build_info::BuildInfo;
+use chrono::{DateTime, SecondsFormat};
+
+const UNKNOWN: &str = "unknown";
+
+/// Details of the running binary.
+pub(crate) fn current() -> BuildInfo {
+    let build_timestamp = stamped(option_env!("BUILD_TIMESTAMP")).and_then(timestamp);
+    let profile = stamped(option_env!("BUILD_PROFILE")).unwrap_or(if cfg!(debug_assertions) {
+        "dev"
+    } else {
+        "release"
+    });
+    BuildInfo::new(
+        env!("CARGO_PKG_VERSION").to_string(),
+        stamped(option_env!("GIT_COMMIT"))
+            .unwrap_or(UNKNOWN)
+            .to_string(),
+        build_timestamp.unwrap_or_else(|| UNKNOWN.to_string()),
+        stamped(option_env!("RUSTC_VERSION"))
+            .map(|version| version.trim_start_matches("rustc "))
+            .unwrap_or(UNKNOWN)
+            .to_string(),
+        profile.to_string(),
+    )
+}
+
+/// The `BUILD_TIMESTAMP` of a stamped build, in seconds since the epoch, as
+/// an RFC 3339 time.
+fn timestamp(secs: &str) -> Option<String> {
+    let at = DateTime::from_timestamp(secs.parse().ok()?, 0)?;
+    Some(at.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// A stamped value, or `None` if the variable is unset or still holds its
+/// `{KEY}` placeholder because the build was not stamped.
+fn stamped(value: Option<&'static str>) -> Option<&'static str> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && !value.starts_with('{'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unstamped_values_are_ignored() {
+        assert_eq!(stamped(Some("{STABLE_GIT_COMMIT}")), None);
+        assert_eq!(stamped(Some("")), None);
+        assert_eq!(stamped(None), None);
+        assert_eq!(stamped(Some("4f1c2e9\n")), Some("4f1c2e9"));
+    }
+
+    #[test]
+    fn stamped_values_are_reported() {
+        // As substituted into build_info.env by `bazel build --stamp`.
+        assert_eq!(stamped(Some("4f1c2e9a0b7d")), Some("4f1c2e9a0b7d"));
+        assert_eq!(
+            stamped(Some("1714525200")).and_then(timestamp),
+            Some("2024-05-01T01:00:00Z".to_string())
+        );
+        assert_eq!(timestamp("not a time"), None);
+    }
+
+    #[test]
+    fn current_reports_the_crate_version() {
+        let info = current();
+
+        assert_eq!(info.version(), env!("CARGO_PKG_VERSION"));
+        assert!(["dev", "release"].contains(&info.profile()));
+        assert!(!info.rustc_version().starts_with("rustc "));
+        let json = serde_json::to_value(&info).unwrap();
+        for field in ["git_commit", "build_timestamp", "rustc_version"] {
+            assert!(json[field].is_string(), "{}", field);
+        }
+    }
+}
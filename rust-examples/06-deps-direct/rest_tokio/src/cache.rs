@@ -0,0 +1,226 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors;
+use crate::errors::CacheError;
+use crate::types::content_hash;
+use crate::types::data_set::DataSet;
+use crate::types::quarantine::Rejection;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// File name of the cached data set inside the cache directory.
+const CACHE_FILE: &str = "data_set.json";
+/// Directory of the rejected data sets inside the cache directory.
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// Keeps the last good data set on disk, so that the service can start with
+/// it when the data source is down.
+///
+/// The file is written to a temporary file first and then renamed over the
+/// previous one, so a crash mid-write never leaves a half-written cache. A
+/// checksum over the content detects files that were damaged on disk.
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize)]
+struct CacheEntryRef<'a> {
+    checksum: String,
+    data_set: &'a DataSet,
+}
+
+#[derive(Serialize)]
+struct QuarantineEntryRef<'a> {
+    #[serde(flatten)]
+    rejection: &'a Rejection,
+    data_set: &'a DataSet,
+}
+
+#[derive(Deserialize)]
+struct CacheEntry {
+    checksum: String,
+    data_set: DataSet,
+}
+
+impl SnapshotCache {
+    pub(crate) fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    pub(crate) fn path(&self) -> PathBuf {
+        self.dir.join(CACHE_FILE)
+    }
+
+    /// Replaces the cached data set.
+    pub(crate) async fn store(&self, data_set: &DataSet) -> Result<(), CacheError> {
+        let entry = CacheEntryRef {
+            checksum: checksum(data_set)?,
+            data_set,
+        };
+        let body = serde_json::to_vec(&entry).map_err(|e| CacheError::Encode(Arc::new(e)))?;
+        write_file(&self.dir, &self.path(), &body).await
+    }
+
+    /// Writes a rejected data set and the reasons for the rejection to
+    /// `quarantine/<hash>.json`, so that it can be inspected. Returns the path.
+    pub(crate) async fn quarantine(
+        &self,
+        data_set: &DataSet,
+        rejection: &Rejection,
+    ) -> Result<PathBuf, CacheError> {
+        let entry = QuarantineEntryRef {
+            rejection,
+            data_set,
+        };
+        let body =
+            serde_json::to_vec_pretty(&entry).map_err(|e| CacheError::Encode(Arc::new(e)))?;
+        let dir = self.dir.join(QUARANTINE_DIR);
+        let path = dir.join(format!("{}.json", data_set.stats().hash()));
+        write_file(&dir, &path, &body).await?;
+        Ok(path)
+    }
+
+    /// Reads the cached data set. Returns `None` if nothing has been cached yet.
+    pub(crate) async fn load(&self) -> Result<Option<DataSet>, CacheError> {
+        let path = self.path();
+        let body = match tokio::fs::read(&path).await {
+            Ok(body) => body,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(CacheError::Read {
+                    path,
+                    source: Arc::new(e),
+                });
+            }
+        };
+
+        let entry: CacheEntry = match serde_json::from_slice(&body) {
+            Ok(entry) => entry,
+            Err(e) => {
+                return Err(CacheError::Decode {
+                    path,
+                    source: Arc::new(e),
+                });
+            }
+        };
+        if checksum(&entry.data_set)? != entry.checksum {
+            return Err(CacheError::Checksum { path });
+        }
+        Ok(Some(entry.data_set))
+    }
+
+    /// Like [`load`](Self::load), but a damaged cache is logged and ignored.
+    pub(crate) async fn restore(&self) -> Option<DataSet> {
+        match self.load().await {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!(error = %errors::report(&e), "Ignoring disk cache");
+                None
+            }
+        }
+    }
+}
+
+/// Replaces `path` in `dir` through a temporary file and a rename.
+async fn write_file(dir: &Path, path: &Path, body: &[u8]) -> Result<(), CacheError> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| CacheError::Write {
+            path: dir.to_path_buf(),
+            source: Arc::new(e),
+        })?;
+    let temp = path.with_extension("json.tmp");
+    let write = async {
+        let mut file = tokio::fs::File::create(&temp).await?;
+        file.write_all(body).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&temp, path).await?;
+        // Persist the rename itself. Windows cannot open a directory as a file.
+        #[cfg(unix)]
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+        Ok::<(), std::io::Error>(())
+    };
+    write.await.map_err(|e| CacheError::Write {
+        path: path.to_path_buf(),
+        source: Arc::new(e),
+    })
+}
+
+fn checksum(data_set: &DataSet) -> Result<String, CacheError> {
+    content_hash::content_hash(data_set)
+        .map(content_hash::to_hex)
+        .map_err(|e| CacheError::Encode(Arc::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::Utc;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cache_{}_{}", name, std::process::id()))
+    }
+
+    fn data_set() -> DataSet {
+        let payload: Payload =
+            serde_json::from_str(r#"{"assets": [{"id": "BTC", "price": 0.1}]}"#).unwrap();
+        DataSet::from_payload(payload, Utc::now()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn store_and_load_round_trip() {
+        let dir = temp_dir("round_trip");
+        let cache = SnapshotCache::new(&dir);
+
+        let expected = data_set();
+
+        let empty = cache.load().await.unwrap();
+        cache.store(&expected).await.unwrap();
+        let loaded = cache.load().await.unwrap();
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(empty.is_none());
+        assert_eq!(loaded, Some(expected));
+        // The temporary file was renamed, not left behind.
+        assert_eq!(leftovers, 1);
+    }
+
+    #[tokio::test]
+    async fn load_rejects_damaged_file() {
+        let dir = temp_dir("damaged");
+        let cache = SnapshotCache::new(&dir);
+        cache.store(&data_set()).await.unwrap();
+
+        let body = std::fs::read_to_string(cache.path()).unwrap();
+        std::fs::write(cache.path(), body.replace("BTC", "ETH")).unwrap();
+        let damaged = cache.load().await;
+        std::fs::write(cache.path(), &body[..body.len() / 2]).unwrap();
+        let truncated = cache.load().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(damaged, Err(CacheError::Checksum { .. })));
+        assert!(truncated.is_err());
+    }
+}
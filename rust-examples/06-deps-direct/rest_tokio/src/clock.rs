@@ -0,0 +1,132 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Source of the current time for the reload schedule, the load status,
+/// readiness and notifications.
+///
+/// The service runs on the [`SystemClock`]. Tests run on a `FakeClock` that
+/// only moves when told to, so that schedules and staleness can be tested
+/// without waiting.
+pub(crate) trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Completes once this clock has reached `deadline`.
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// A clock shared by the reloader, its scheduler and the routes.
+pub(crate) type SharedClock = Arc<dyn Clock>;
+
+/// The wall clock. Sleeps run on tokio timers and do not follow changes of
+/// the system time.
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let delay = (deadline - Utc::now()).to_std().unwrap_or_default();
+        Box::pin(tokio::time::sleep(delay))
+    }
+}
+
+/// A clock that stands still until it is set forward.
+#[cfg(test)]
+pub(crate) struct FakeClock {
+    now: tokio::sync::watch::Sender<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub(crate) fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: tokio::sync::watch::Sender::new(start),
+        }
+    }
+
+    /// Moves the clock forward to `to` and wakes the sleepers whose deadline
+    /// has been reached. The clock never goes back.
+    pub(crate) fn set(&self, to: DateTime<Utc>) {
+        self.now.send_if_modified(|now| {
+            let later = to > *now;
+            if later {
+                *now = to;
+            }
+            later
+        });
+    }
+
+    pub(crate) fn advance(&self, by: std::time::Duration) {
+        self.set(self.now() + by);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            // A dropped clock never reaches the deadline.
+            if now.wait_for(|now| *now >= deadline).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn fake_clock_wakes_sleepers_at_their_deadline() {
+        let clock = FakeClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let mut sleep = clock.sleep_until(clock.now() + Duration::from_secs(60));
+
+        clock.advance(Duration::from_secs(59));
+        assert!(futures_util::poll!(&mut sleep).is_pending());
+
+        clock.advance(Duration::from_secs(1));
+        sleep.await;
+        assert_eq!(
+            clock.now(),
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 1, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn fake_clock_does_not_go_back() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = FakeClock::new(start);
+
+        clock.set(start - Duration::from_secs(1));
+
+        assert_eq!(clock.now(), start);
+        clock.sleep_until(start).await;
+    }
+}
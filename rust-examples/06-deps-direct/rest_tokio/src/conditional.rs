@@ -0,0 +1,235 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::ApiError;
+use crate::types::data_set::DataSet;
+use chrono::{DateTime, Utc};
+use warp::Filter;
+use warp::Reply;
+use warp::http::StatusCode;
+use warp::http::header::{ETAG, HeaderValue, LAST_MODIFIED, VARY};
+
+/// Format of `Last-Modified` and `If-Modified-Since`, the IMF-fixdate of RFC 9110.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Conditional request headers of a `GET`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Preconditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+/// Extracts the conditional request headers.
+pub(crate) fn preconditions()
+-> impl Filter<Extract = (Preconditions,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("if-none-match")
+        .and(warp::header::optional::<String>("if-modified-since"))
+        .map(|if_none_match, if_modified_since| Preconditions {
+            if_none_match,
+            if_modified_since,
+        })
+}
+
+/// Validators of a response served from a data set.
+///
+/// The entity tag is the data set hash, so it changes exactly when the data
+/// changes. It is weak because the same data can be sent with different
+/// encodings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Validators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// `last_modified` is the time the data set was swapped in, if known.
+    pub(crate) fn new(data_set: &DataSet, last_modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            etag: format!("W/\"{}\"", data_set.stats().hash()),
+            last_modified,
+        }
+    }
+
+    /// Validators of a response derived from data sets rather than served
+    /// from one, with an entity tag built from their hashes. Such a response
+    /// has no single swap time, so it carries no `Last-Modified`.
+    pub(crate) fn derived(tag: &str) -> Self {
+        Self {
+            etag: format!("W/\"{}\"", tag),
+            last_modified: None,
+        }
+    }
+}
+
+impl Preconditions {
+    /// Whether the client's copy is current, following RFC 9110 section 13.2.2:
+    /// `If-None-Match` takes precedence, and `If-Modified-Since` is only
+    /// evaluated without it. A date that does not parse is ignored.
+    fn not_modified(&self, validators: &Validators) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || opaque_tag(tag) == opaque_tag(&validators.etag));
+        }
+        let since = self
+            .if_modified_since
+            .as_deref()
+            .and_then(|since| DateTime::parse_from_rfc2822(since).ok());
+        match (since, validators.last_modified) {
+            (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+}
+
+/// Strips the weak prefix for the weak comparison of entity tags.
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// Answers `304 Not Modified` if the client's copy is current, and the reply
+/// built by `reply` otherwise. Both carry the validators.
+pub(crate) fn reply<R, F>(
+    preconditions: &Preconditions,
+    validators: &Validators,
+    reply: F,
+) -> Result<warp::reply::Response, ApiError>
+where
+    R: Reply,
+    F: FnOnce() -> Result<R, ApiError>,
+{
+    let mut response = if preconditions.not_modified(validators) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        reply()?.into_response()
+    };
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&validators.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Some(last_modified) = validators.last_modified {
+            let date = last_modified.format(HTTP_DATE).to_string();
+            if let Ok(date) = HeaderValue::from_str(&date) {
+                headers.insert(LAST_MODIFIED, date);
+            }
+        }
+    }
+    Ok(response)
+}
+
+/// Like [`reply`], for the routes that negotiate the content coding. Their
+/// answers, a `304` or a body that is not compressed included, share the
+/// `ETag` of every coding, so they all carry `Vary: accept-encoding`.
+pub(crate) fn negotiated_reply<R, F>(
+    preconditions: &Preconditions,
+    validators: &Validators,
+    reply: F,
+) -> Result<warp::reply::Response, ApiError>
+where
+    R: Reply,
+    F: FnOnce() -> Result<R, ApiError>,
+{
+    let mut response = self::reply(preconditions, validators, reply)?;
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept-encoding"));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::TimeZone;
+
+    fn validators() -> Validators {
+        let data_set = DataSet::from_payload(Payload::default(), Utc::now()).unwrap();
+        Validators::new(
+            &data_set,
+            Some(Utc.with_ymd_and_hms(2024, 5, 1, 1, 0, 0).unwrap()),
+        )
+    }
+
+    fn status(if_none_match: Option<&str>, if_modified_since: Option<&str>) -> StatusCode {
+        let preconditions = Preconditions {
+            if_none_match: if_none_match.map(str::to_string),
+            if_modified_since: if_modified_since.map(str::to_string),
+        };
+        reply(&preconditions, &validators(), || Ok("body"))
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn if_none_match_compares_entity_tags() {
+        let etag = validators().etag;
+        let strong = etag.trim_start_matches("W/").to_string();
+
+        assert_eq!(status(Some(&etag), None), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(Some(&strong), None), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            status(Some(&format!("\"other\", {}", etag)), None),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(status(Some("*"), None), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(Some("\"other\""), None), StatusCode::OK);
+        // If-None-Match takes precedence over If-Modified-Since.
+        assert_eq!(
+            status(Some("\"other\""), Some("Wed, 01 May 2024 02:00:00 GMT")),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn if_modified_since_compares_swap_time() {
+        assert_eq!(
+            status(None, Some("Wed, 01 May 2024 01:00:00 GMT")),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(
+            status(None, Some("Wed, 01 May 2024 00:59:59 GMT")),
+            StatusCode::OK
+        );
+        assert_eq!(status(None, Some("yesterday")), StatusCode::OK);
+        assert_eq!(status(None, None), StatusCode::OK);
+    }
+
+    #[test]
+    fn responses_carry_the_validators() {
+        let response = reply(&Preconditions::default(), &validators(), || Ok("body")).unwrap();
+
+        assert_eq!(
+            response.headers()[LAST_MODIFIED],
+            "Wed, 01 May 2024 01:00:00 GMT"
+        );
+        assert_eq!(response.headers()[ETAG], validators().etag.as_str());
+    }
+
+    #[test]
+    fn negotiated_replies_vary_with_accept_encoding() {
+        let current = Preconditions {
+            if_none_match: Some(validators().etag),
+            if_modified_since: None,
+        };
+        for preconditions in [Preconditions::default(), current] {
+            let response = negotiated_reply(&preconditions, &validators(), || Ok("body")).unwrap();
+            assert_eq!(response.headers()[VARY], "accept-encoding");
+        }
+        let response = reply(&Preconditions::default(), &validators(), || Ok("body")).unwrap();
+        assert!(response.headers().get(VARY).is_none());
+    }
+}
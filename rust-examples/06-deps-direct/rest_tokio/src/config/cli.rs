@@ -0,0 +1,109 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::config::{AccessLogFormat, LogFormat};
+use clap::Parser;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Command line flags. Every flag overrides the matching entry from the
+/// config file and the `REST_*` environment variables.
+#[derive(Debug, Default, Clone, Parser)]
+#[command(about = "Sample REST service serving reference data")]
+pub struct Cli {
+    /// Path to a TOML config file [env: REST_CONFIG]
+    #[arg(long, value_name = "FILE")]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Print the merged configuration as TOML and exit
+    #[arg(long)]
+    pub(crate) print_config: bool,
+
+    /// Address the server binds to [env: REST_SERVER_BIND_ADDRESS]
+    #[arg(long, value_name = "IP")]
+    pub(crate) bind_address: Option<IpAddr>,
+
+    /// Port the server listens on [env: REST_SERVER_PORT]
+    #[arg(long)]
+    pub(crate) port: Option<u16>,
+
+    /// Seconds to wait for in-flight requests and reloads on shutdown [env: REST_SERVER_SHUTDOWN_TIMEOUT_SECS]
+    #[arg(long, value_name = "SECS")]
+    pub(crate) shutdown_timeout_secs: Option<u64>,
+
+    /// Data source: `builtin`, a file path, or an http(s) URL [env: REST_DATA_SOURCE]
+    #[arg(long, value_name = "LOCATION")]
+    pub(crate) data_source: Option<String>,
+
+    /// Number of loaded data sets kept for rollback [env: REST_DATA_HISTORY_SIZE]
+    #[arg(long, value_name = "N")]
+    pub(crate) history_size: Option<usize>,
+
+    /// Directory the last good data set is cached in for warm starts [env: REST_DATA_CACHE_DIR]
+    #[arg(long, value_name = "DIR")]
+    pub(crate) cache_dir: Option<PathBuf>,
+
+    /// Cron expression (sec min hour day month weekday) of the data reload [env: REST_RELOAD_SCHEDULE]
+    #[arg(long, value_name = "CRON")]
+    pub(crate) reload_schedule: Option<String>,
+
+    /// IANA timezone of the reload schedule, i.e. `Europe/Berlin` [env: REST_RELOAD_TIMEZONE]
+    #[arg(long, value_name = "TZ")]
+    pub(crate) reload_timezone: Option<String>,
+
+    /// Upper bound of the random delay added to each reload [env: REST_RELOAD_JITTER_SECS]
+    #[arg(long, value_name = "SECS")]
+    pub(crate) reload_jitter_secs: Option<u64>,
+
+    /// Retries of a failed scheduled reload [env: REST_RELOAD_RETRY_ATTEMPTS]
+    #[arg(long, value_name = "N")]
+    pub(crate) reload_retry_attempts: Option<u32>,
+
+    /// Log filter directives, i.e. `info` or `info,warp=debug` [env: REST_LOG_LEVEL]
+    #[arg(long, value_name = "FILTER")]
+    pub(crate) log_level: Option<String>,
+
+    /// Log output format [env: REST_LOG_FORMAT]
+    #[arg(long, value_name = "FORMAT")]
+    pub(crate) log_format: Option<LogFormat>,
+
+    /// Access log format [env: REST_LOG_ACCESS]
+    #[arg(long, value_name = "FORMAT")]
+    pub(crate) access_log: Option<AccessLogFormat>,
+
+    /// Bearer token of the /admin routes [env: REST_ADMIN_TOKEN]
+    #[arg(long, value_name = "TOKEN")]
+    pub(crate) admin_token: Option<String>,
+
+    /// Seconds after the last successful load until /ready fails, 0 disables [env: REST_HEALTH_MAX_STALENESS_SECS]
+    #[arg(long, value_name = "SECS")]
+    pub(crate) max_staleness_secs: Option<u64>,
+
+    /// URL that receives reload failure and recovery notifications [env: REST_NOTIFY_WEBHOOK_URL]
+    #[arg(long, value_name = "URL")]
+    pub(crate) notify_webhook_url: Option<String>,
+
+    /// Program run with the notification on stdin [env: REST_NOTIFY_COMMAND]
+    #[arg(long, value_name = "PATH")]
+    pub(crate) notify_command: Option<String>,
+
+    /// Consecutive failed reloads before a notification is sent [env: REST_NOTIFY_FAILURE_THRESHOLD]
+    #[arg(long, value_name = "N")]
+    pub(crate) notify_failure_threshold: Option<u32>,
+
+    /// Shorthand for `--log-level debug`
+    #[arg(long)]
+    pub(crate) verbose: bool,
+}
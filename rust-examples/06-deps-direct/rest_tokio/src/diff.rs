@@ -0,0 +1,172 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::asset::Asset;
+use crate::types::data_set::DataSet;
+use crate::types::data_set_diff::{DataSetDiff, FieldChange, RecordDiff};
+use crate::types::exchange::Exchange;
+use crate::types::instrument::Instrument;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// A record that is identified by its id across data sets.
+trait Record: PartialEq {
+    fn record_id(&self) -> &str;
+}
+
+impl Record for Asset {
+    fn record_id(&self) -> &str {
+        self.id()
+    }
+}
+
+impl Record for Exchange {
+    fn record_id(&self) -> &str {
+        self.id()
+    }
+}
+
+impl Record for Instrument {
+    fn record_id(&self) -> &str {
+        self.id()
+    }
+}
+
+impl<T: Record> Record for &T {
+    fn record_id(&self) -> &str {
+        (*self).record_id()
+    }
+}
+
+/// Compares two data sets record by record.
+///
+/// Records are matched by id; a record present in both with any differing
+/// field is modified. As in the lookups, the first of several records with
+/// the same id counts. Ids are listed in the order of the data set they are
+/// taken from.
+pub(crate) fn diff(from: &DataSet, to: &DataSet) -> DataSetDiff {
+    DataSetDiff::new(
+        from.stats().hash().to_string(),
+        to.stats().hash().to_string(),
+        stats(from, to),
+        records(from.assets(), to.assets()),
+        records(from.exchanges(), to.exchanges()),
+        records(&from.instruments(None, None), &to.instruments(None, None)),
+    )
+}
+
+fn stats(from: &DataSet, to: &DataSet) -> Vec<FieldChange> {
+    let (Ok(Value::Object(from)), Ok(Value::Object(to))) = (
+        serde_json::to_value(from.stats()),
+        serde_json::to_value(to.stats()),
+    ) else {
+        return Vec::new();
+    };
+    from.into_iter()
+        .filter_map(|(field, old)| {
+            let new = to.get(&field).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange::new(field, old, new))
+        })
+        .collect()
+}
+
+fn records<T: Record>(from: &[T], to: &[T]) -> RecordDiff {
+    let old = by_id(from);
+    let new = by_id(to);
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for (id, record) in unique(to) {
+        match old.get(id) {
+            None => added.push(id.to_string()),
+            Some(previous) if *previous != record => modified.push(id.to_string()),
+            Some(_) => {}
+        }
+    }
+    let removed = unique(from)
+        .filter(|(id, _)| !new.contains_key(id))
+        .map(|(id, _)| id.to_string())
+        .collect();
+
+    RecordDiff::new(from.len(), to.len(), added, removed, modified)
+}
+
+fn by_id<T: Record>(records: &[T]) -> HashMap<&str, &T> {
+    unique(records).collect()
+}
+
+/// The records in list order, skipping all but the first with the same id.
+fn unique<T: Record>(records: &[T]) -> impl Iterator<Item = (&str, &T)> {
+    let mut seen = HashSet::new();
+    records
+        .iter()
+        .map(|record| (record.record_id(), record))
+        .filter(move |(id, _)| seen.insert(*id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::{TimeZone, Utc};
+
+    fn data_set(json: &str) -> DataSet {
+        let payload: Payload = serde_json::from_str(json).unwrap();
+        DataSet::from_payload(payload, Utc.with_ymd_and_hms(2024, 5, 1, 1, 0, 0).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn finds_added_removed_and_modified_records() {
+        let from = data_set(
+            r#"{"assets":[{"id":"BTC"},{"id":"ETH"},{"id":"EUR","name":"Euro"}],"exchanges":[{"id":"KRAKEN"}],"instruments":[]}"#,
+        );
+        let to = data_set(
+            r#"{"assets":[{"id":"BTC"},{"id":"EUR","name":"EUR"},{"id":"USD"},{"id":"USD","name":"dup"}],"exchanges":[{"id":"KRAKEN"}],"instruments":[]}"#,
+        );
+
+        let diff = diff(&from, &to);
+        let json = serde_json::to_value(&diff).unwrap();
+
+        assert_eq!(
+            json["assets"],
+            serde_json::json!({
+                "count_from": 3,
+                "count_to": 4,
+                "count_delta": 1,
+                "added": ["USD"],
+                "removed": ["ETH"],
+                "modified": ["EUR"],
+            })
+        );
+        assert_eq!(json["exchanges"]["count_delta"], 0);
+        assert_eq!(
+            diff.to_string(),
+            "assets 4 (+1 -1 ~1), exchanges 1 (+0 -0 ~0), instruments 0 (+0 -0 ~0)"
+        );
+    }
+
+    #[test]
+    fn lists_changed_stats_fields() {
+        let from = data_set(r#"{"assets":[{"id":"BTC"}],"exchanges":[],"instruments":[]}"#);
+        let to = data_set(r#"{"assets":[{"id":"ETH"}],"exchanges":[],"instruments":[]}"#);
+
+        let changed = serde_json::to_value(diff(&from, &to)).unwrap();
+        let unchanged = serde_json::to_value(diff(&from, &from)).unwrap();
+
+        assert_eq!(changed["stats"][0]["field"], "hash");
+        assert_eq!(changed["stats"].as_array().unwrap().len(), 1);
+        assert_eq!(unchanged["stats"], serde_json::json!([]));
+    }
+}
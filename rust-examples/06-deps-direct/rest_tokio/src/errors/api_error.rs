@@ -0,0 +1,128 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors;
+use crate::errors::{LoadError, QueryError};
+use crate::types::problem::ErrorCode;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use warp::http::{Method, StatusCode};
+use warp::reject::Reject;
+
+/// Errors a handler answers with. The rejection handler turns them into
+/// problem responses.
+#[derive(Debug)]
+pub enum ApiError {
+    /// No route at the path, or no record with the id or hash in the path.
+    NotFound(String),
+    /// The route only answers these methods.
+    MethodNotAllowed(&'static [Method]),
+    InvalidQuery(QueryError),
+    /// The data set with this hash is no longer kept.
+    CursorExpired(String),
+    /// The snapshot with this hash is pinned.
+    SnapshotPinned(String),
+    InvalidBody(String),
+    /// An on-demand reload failed.
+    Reload(LoadError),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            Self::InvalidQuery(_) | Self::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            Self::CursorExpired(_) => StatusCode::GONE,
+            Self::SnapshotPinned(_) => StatusCode::CONFLICT,
+            Self::Reload(LoadError::Timeout { .. }) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Reload(_) => StatusCode::BAD_GATEWAY,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Explanation for the client. For failed reloads it includes the causes.
+    pub fn detail(&self) -> String {
+        match self {
+            Self::Reload(e) => errors::report(e),
+            other => other.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::MethodNotAllowed(_) => ErrorCode::MethodNotAllowed,
+            Self::InvalidQuery(_) => ErrorCode::InvalidQuery,
+            Self::CursorExpired(_) => ErrorCode::CursorExpired,
+            Self::SnapshotPinned(_) => ErrorCode::SnapshotPinned,
+            Self::InvalidBody(_) => ErrorCode::InvalidBody,
+            Self::Reload(e) => e.code(),
+            Self::Internal(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "{}", what),
+            Self::MethodNotAllowed(methods) => {
+                let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+                write!(f, "This route only answers {}", methods.join(", "))
+            }
+            Self::InvalidQuery(e) => write!(f, "{}", e),
+            Self::CursorExpired(hash) => write!(
+                f,
+                "Data set {} of the cursor is no longer available; start again without a cursor",
+                hash
+            ),
+            Self::SnapshotPinned(hash) => write!(
+                f,
+                "Snapshot {} is pinned; unpin it with DELETE /admin/pin to reload",
+                hash
+            ),
+            Self::InvalidBody(reason) => write!(f, "{}", reason),
+            Self::Reload(_) => write!(f, "Reload failed"),
+            Self::Internal(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for ApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidQuery(e) => Some(e),
+            Self::Reload(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Reject for ApiError {}
+
+impl From<QueryError> for ApiError {
+    fn from(e: QueryError) -> Self {
+        Self::InvalidQuery(e)
+    }
+}
+
+impl From<LoadError> for ApiError {
+    fn from(e: LoadError) -> Self {
+        Self::Reload(e)
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::QueryError;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum CacheError {
+    /// Reading a cache file failed.
+    Read {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    /// Creating the cache directory or replacing a cache file failed.
+    Write {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    /// A cache file is not a valid cache entry.
+    Decode {
+        path: PathBuf,
+        source: Arc<serde_json::Error>,
+    },
+    /// The data set of a cache file does not match its checksum.
+    Checksum { path: PathBuf },
+    /// A data set or response body cannot be turned into JSON.
+    Encode(Arc<serde_json::Error>),
+    /// A response body cannot be compressed.
+    Compress(Arc<io::Error>),
+    /// A cached list page cannot be built.
+    Listing(QueryError),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, .. } => write!(f, "Failed to read {}", path.display()),
+            Self::Write { path, .. } => write!(f, "Failed to write {}", path.display()),
+            Self::Decode { path, .. } => write!(f, "Invalid cache {}", path.display()),
+            Self::Checksum { path } => write!(f, "Checksum mismatch in {}", path.display()),
+            Self::Encode(_) => write!(f, "Failed to encode data"),
+            Self::Compress(_) => write!(f, "Failed to compress body"),
+            Self::Listing(_) => write!(f, "Failed to build list page"),
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Read { source, .. } | Self::Write { source, .. } => Some(source.as_ref()),
+            Self::Decode { source, .. } => Some(source.as_ref()),
+            Self::Checksum { .. } => None,
+            Self::Encode(source) => Some(source.as_ref()),
+            Self::Compress(source) => Some(source.as_ref()),
+            Self::Listing(source) => Some(source),
+        }
+    }
+}
+
+impl From<QueryError> for CacheError {
+    fn from(e: QueryError) -> Self {
+        Self::Listing(e)
+    }
+}
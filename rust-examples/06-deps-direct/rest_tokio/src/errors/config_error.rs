@@ -0,0 +1,202 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors;
+use crate::errors::LoadError;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::TryInitError;
+
+#[derive(Debug, Clone)]
+pub enum ConfigError {
+    /// Settings that cannot be used, all problems of the config at once.
+    Invalid(Vec<ConfigProblem>),
+    /// The key is not a config key.
+    UnknownKey(String),
+    /// The value cannot be parsed as the setting of `key`.
+    Value {
+        key: String,
+        value: String,
+        source: Arc<dyn Error + Send + Sync>,
+    },
+    /// Reading the config file failed.
+    Read {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    /// The config is not valid TOML or does not fit the config sections.
+    Parse {
+        path: Option<PathBuf>,
+        source: Arc<toml::de::Error>,
+    },
+    /// The config cannot be written as TOML.
+    Serialize(Arc<toml::ser::Error>),
+    /// An environment variable holds an invalid setting.
+    Env {
+        name: String,
+        source: Box<ConfigError>,
+    },
+    /// The data source location cannot be used.
+    Source { location: String, source: LoadError },
+    /// The reload schedule is not a cron expression.
+    Schedule {
+        expression: String,
+        source: Arc<cron::error::Error>,
+    },
+    /// The reload timezone is not an IANA timezone.
+    Timezone {
+        timezone: String,
+        source: Arc<chrono_tz::ParseError>,
+    },
+    /// The log filter directives cannot be parsed.
+    LogFilter {
+        directives: String,
+        source: Arc<ParseError>,
+    },
+    /// The active log filter cannot be replaced.
+    LogReload(Arc<reload::Error>),
+    /// The global log subscriber cannot be installed.
+    LogSubscriber(Arc<TryInitError>),
+    /// The webhook URL cannot be parsed.
+    WebhookUrl {
+        url: String,
+        source: Arc<dyn Error + Send + Sync>,
+    },
+    /// The webhook URL is not an http or https URL.
+    WebhookScheme(String),
+    /// The HTTP client for the webhook cannot be built.
+    HttpClient(Arc<reqwest::Error>),
+}
+
+/// A setting that cannot be used, found when the merged config is checked.
+#[derive(Debug, Clone)]
+pub enum ConfigProblem {
+    /// The setting `key` is below its smallest value.
+    TooSmall { key: String, min: u64 },
+    /// The setting `key` is above its largest value.
+    TooLarge { key: String, max: u64 },
+    /// The setting `key` is larger than the setting `limit`.
+    ExceedsSetting { key: String, limit: &'static str },
+    /// The name of a data set cannot be used in the `/datasets/{name}` routes.
+    DataSetName(String),
+    /// `admin.token` is empty or contains whitespace.
+    AdminToken,
+    /// The setting or section `key` is invalid for the reason in `source`.
+    Setting { key: String, source: ConfigError },
+    /// The data source of `key` cannot be used.
+    Source { key: String, source: LoadError },
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall { key, min } => write!(f, "{} must be at least {}", key, min),
+            Self::TooLarge { key, max } => write!(f, "{} must not exceed {}", key, max),
+            Self::ExceedsSetting { key, limit } => write!(f, "{} must not exceed {}", key, limit),
+            Self::DataSetName(name) => write!(
+                f,
+                "datasets.{} must be lowercase letters, digits, '-' and '_', and not '{}'",
+                name,
+                crate::registry::DEFAULT
+            ),
+            Self::AdminToken => write!(f, "admin.token must not be empty or contain whitespace"),
+            Self::Setting { key, .. } => write!(f, "{}", key),
+            Self::Source { key, .. } => write!(f, "{} is invalid", key),
+        }
+    }
+}
+
+impl Error for ConfigProblem {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Setting { source, .. } => Some(source),
+            Self::Source { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(problems) => {
+                // Each problem is reported with its causes, so that one line
+                // names everything to fix.
+                let problems: Vec<String> = problems
+                    .iter()
+                    .map(|problem| errors::report(problem))
+                    .collect();
+                write!(f, "{}", problems.join("; "))
+            }
+            Self::UnknownKey(key) => write!(f, "Unknown config key {}", key),
+            Self::Value { key, value, .. } => write!(f, "Invalid value '{}' for {}", value, key),
+            Self::Read { path, .. } => write!(f, "Failed to read {}", path.display()),
+            Self::Parse {
+                path: Some(path), ..
+            } => write!(f, "Invalid config file {}", path.display()),
+            Self::Parse { path: None, .. } => write!(f, "Invalid config"),
+            Self::Serialize(_) => write!(f, "Failed to write config as TOML"),
+            Self::Env { name, .. } => write!(f, "{}", name),
+            Self::Source { location, .. } => write!(f, "data source '{}' is invalid", location),
+            Self::Schedule { expression, .. } => write!(
+                f,
+                "reload.schedule '{}' is not a valid cron expression",
+                expression
+            ),
+            Self::Timezone { timezone, .. } => {
+                write!(f, "reload.timezone '{}' is not an IANA timezone", timezone)
+            }
+            Self::LogFilter { directives, .. } => {
+                write!(f, "Invalid log filter '{}'", directives)
+            }
+            Self::LogReload(_) => write!(f, "Failed to update log filter"),
+            Self::LogSubscriber(_) => write!(f, "Failed to install log subscriber"),
+            Self::WebhookUrl { url, .. } => write!(f, "notify.webhook_url '{}' is invalid", url),
+            Self::WebhookScheme(url) => write!(
+                f,
+                "notify.webhook_url '{}' must be an http or https URL",
+                url
+            ),
+            Self::HttpClient(_) => write!(f, "Failed to build HTTP client"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Invalid(_) | Self::UnknownKey(_) | Self::WebhookScheme(_) => None,
+            Self::Value { source, .. } => Some(source.as_ref()),
+            Self::Read { source, .. } => Some(source.as_ref()),
+            Self::Parse { source, .. } => Some(source.as_ref()),
+            Self::Serialize(source) => Some(source.as_ref()),
+            Self::Env { source, .. } => Some(source.as_ref()),
+            Self::Source { source, .. } => Some(source),
+            Self::Schedule { source, .. } => Some(source.as_ref()),
+            Self::Timezone { source, .. } => Some(source.as_ref()),
+            Self::LogFilter { source, .. } => Some(source.as_ref()),
+            Self::LogReload(source) => Some(source.as_ref()),
+            Self::LogSubscriber(source) => Some(source.as_ref()),
+            Self::WebhookUrl { source, .. } => Some(source.as_ref()),
+            Self::HttpClient(source) => Some(source.as_ref()),
+        }
+    }
+}
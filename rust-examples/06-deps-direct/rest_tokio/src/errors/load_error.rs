@@ -0,0 +1,145 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::problem::ErrorCode;
+use crate::validate::Violation;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Why loading the data from the data source failed.
+///
+/// The underlying errors are shared, so that the result of a reload can be
+/// handed to every caller that waited for it.
+#[derive(Debug, Clone)]
+pub enum LoadError {
+    /// The data source location cannot be used.
+    Location(String),
+    /// Reading the data from the local file system failed.
+    Io {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    /// The HTTP request failed before a response arrived.
+    Http {
+        url: String,
+        source: Arc<reqwest::Error>,
+    },
+    /// The upstream answered with an error status.
+    HttpStatus {
+        url: String,
+        status: u16,
+        source: Arc<reqwest::Error>,
+    },
+    /// The upstream did not answer in time.
+    Timeout {
+        url: String,
+        source: Arc<reqwest::Error>,
+    },
+    /// The data is not a valid payload document.
+    Decode {
+        location: String,
+        source: Arc<serde_json::Error>,
+    },
+    /// The payload decoded, but cannot be hashed.
+    Hash(Arc<serde_json::Error>),
+    /// The validation gate turned down the data set with `hash`.
+    Rejected {
+        hash: String,
+        violations: Vec<Violation>,
+    },
+}
+
+impl LoadError {
+    /// Classifies a failed HTTP request or response.
+    pub fn from_http(url: &str, error: reqwest::Error) -> Self {
+        let url = url.to_string();
+        if let Some(status) = error.status() {
+            Self::HttpStatus {
+                url,
+                status: status.as_u16(),
+                source: Arc::new(error),
+            }
+        } else if error.is_timeout() {
+            Self::Timeout {
+                url,
+                source: Arc::new(error),
+            }
+        } else {
+            Self::Http {
+                url,
+                source: Arc::new(error),
+            }
+        }
+    }
+
+    /// Stable code reported in problem responses.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Location(_) => ErrorCode::SourceLocation,
+            Self::Io { .. } => ErrorCode::SourceIo,
+            Self::Http { .. } => ErrorCode::SourceHttp,
+            Self::HttpStatus { .. } => ErrorCode::SourceHttpStatus,
+            Self::Timeout { .. } => ErrorCode::SourceTimeout,
+            Self::Decode { .. } => ErrorCode::PayloadDecode,
+            Self::Hash(_) | Self::Rejected { .. } => ErrorCode::PayloadInvalid,
+        }
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Location(reason) => write!(f, "{}", reason),
+            Self::Io { path, .. } => write!(f, "Failed to read {}", path.display()),
+            Self::Http { url, .. } => write!(f, "Failed to download {}", url),
+            Self::HttpStatus { url, status, .. } => {
+                write!(f, "Download of {} answered with status {}", url, status)
+            }
+            Self::Timeout { url, .. } => write!(f, "Download of {} timed out", url),
+            Self::Decode { location, .. } => {
+                write!(f, "Failed to decode payload from {}", location)
+            }
+            Self::Hash(_) => write!(f, "Failed to hash payload"),
+            Self::Rejected { hash, violations } => {
+                write!(f, "Invalid payload: data set {} rejected: ", hash)?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Location(_) | Self::Rejected { .. } => None,
+            Self::Io { source, .. } => Some(source.as_ref()),
+            Self::Http { source, .. }
+            | Self::HttpStatus { source, .. }
+            | Self::Timeout { source, .. } => Some(source.as_ref()),
+            Self::Decode { source, .. } => Some(source.as_ref()),
+            Self::Hash(source) => Some(source.as_ref()),
+        }
+    }
+}
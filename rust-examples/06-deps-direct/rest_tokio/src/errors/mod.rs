@@ -13,6 +13,32 @@
 // limitations under the License.
 
 
-pub(crate) mod init_error;
+use std::error::Error;
 
-pub(crate) use crate::errors::init_error::InitError;
+pub(crate) mod api_error;
+pub(crate) mod cache_error;
+pub(crate) mod config_error;
+pub(crate) mod load_error;
+pub(crate) mod notify_error;
+pub(crate) mod query_error;
+
+pub(crate) use crate::errors::api_error::ApiError;
+pub(crate) use crate::errors::cache_error::CacheError;
+pub(crate) use crate::errors::config_error::ConfigError;
+pub(crate) use crate::errors::config_error::ConfigProblem;
+pub(crate) use crate::errors::load_error::LoadError;
+pub(crate) use crate::errors::notify_error::NotifyError;
+pub(crate) use crate::errors::query_error::QueryError;
+
+/// Formats an error with the chain of its sources, i.e.
+/// `Failed to read data.json: No such file or directory (os error 2)`.
+pub(crate) fn report(error: &dyn Error) -> String {
+    let mut report = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        report.push_str(": ");
+        report.push_str(&error.to_string());
+        source = error.source();
+    }
+    report
+}
@@ -0,0 +1,70 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum NotifyError {
+    /// The notification cannot be turned into JSON.
+    Encode(Arc<serde_json::Error>),
+    /// The webhook did not accept the notification.
+    Webhook {
+        url: String,
+        source: Arc<reqwest::Error>,
+    },
+    /// The notify command cannot be started or its input not be written.
+    Command {
+        command: PathBuf,
+        source: Arc<io::Error>,
+    },
+    /// The notify command did not finish in time.
+    CommandTimeout { command: PathBuf },
+    /// The notify command exited with an error status.
+    CommandFailed {
+        command: PathBuf,
+        status: ExitStatus,
+    },
+}
+
+impl Display for NotifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encode(_) => write!(f, "Failed to encode notification"),
+            Self::Webhook { url, .. } => write!(f, "Failed to post to {}", url),
+            Self::Command { command, .. } => write!(f, "Failed to run {}", command.display()),
+            Self::CommandTimeout { command } => write!(f, "{} timed out", command.display()),
+            Self::CommandFailed { command, status } => {
+                write!(f, "{} exited with {}", command.display(), status)
+            }
+        }
+    }
+}
+
+impl Error for NotifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Encode(source) => Some(source.as_ref()),
+            Self::Webhook { source, .. } => Some(source.as_ref()),
+            Self::Command { source, .. } => Some(source.as_ref()),
+            Self::CommandTimeout { .. } | Self::CommandFailed { .. } => None,
+        }
+    }
+}
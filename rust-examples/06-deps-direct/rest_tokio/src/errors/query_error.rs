@@ -0,0 +1,75 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum QueryError {
+    /// The page size is not a number in the allowed range.
+    Limit { value: String, max: usize },
+    /// The cursor was not issued by this service.
+    Cursor(String),
+    /// The cursor points behind the last record of the list.
+    CursorPastEnd { total: usize },
+    /// A `sort` or `fields` parameter is not a list of field names.
+    FieldList {
+        parameter: &'static str,
+        list: String,
+    },
+    /// A field of a `sort` or `fields` parameter is not a valid name.
+    Field {
+        parameter: &'static str,
+        field: String,
+    },
+    /// A record cannot be turned into JSON.
+    Serialize(Arc<serde_json::Error>),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Limit { value, max } => {
+                write!(f, "limit '{}' must be a number from 1 to {}", value, max)
+            }
+            Self::Cursor(cursor) => write!(f, "cursor '{}' is not valid", cursor),
+            Self::CursorPastEnd { total } => {
+                write!(f, "cursor is past the end of the list of {} records", total)
+            }
+            Self::FieldList { parameter, list } => write!(
+                f,
+                "{} '{}' must be a comma separated list of field names",
+                parameter, list
+            ),
+            Self::Field { parameter, field } => write!(
+                f,
+                "{} field '{}' is not a valid field name",
+                parameter, field
+            ),
+            Self::Serialize(_) => write!(f, "Failed to serialize record"),
+        }
+    }
+}
+
+impl Error for QueryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Serialize(source) => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,384 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::data_event::DataEvent;
+use crate::types::events_query::EventsQuery;
+use crate::types::stats::Stats;
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt, stream};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+use warp::Filter;
+use warp::sse::Event;
+use warp::ws::{Message, WebSocket};
+
+/// Change events kept for clients that reconnect with `Last-Event-ID`.
+const REPLAY_SIZE: usize = 64;
+
+/// Broadcasts data set changes to the `/events` and `/ws` subscribers.
+///
+/// The latest events are kept, so a client that reconnects with the id of
+/// the last event it saw gets the ones it missed replayed. Ids start at the
+/// start time of the process in milliseconds, so that an id from before a
+/// restart is recognized as unknown and answered with a resync.
+#[derive(Debug)]
+pub(crate) struct Events {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    latest_id: u64,
+    stats: Stats,
+    replay: VecDeque<DataEvent>,
+    /// `None` once closed for shutdown.
+    sender: Option<broadcast::Sender<DataEvent>>,
+}
+
+impl Events {
+    /// Starts without events; `stats` are those of the active data set.
+    pub(crate) fn new(stats: Stats) -> Self {
+        let (sender, _) = broadcast::channel(REPLAY_SIZE);
+        Self {
+            state: Mutex::new(State {
+                latest_id: Utc::now().timestamp_millis().unsigned_abs(),
+                stats,
+                replay: VecDeque::with_capacity(REPLAY_SIZE),
+                sender: Some(sender),
+            }),
+        }
+    }
+
+    /// Announces that the data set with `old_hash` was replaced by one with `stats`.
+    pub(crate) fn publish(&self, old_hash: &str, stats: Stats) {
+        let mut state = self.state.lock().expect("events lock poisoned");
+        state.latest_id += 1;
+        let event = DataEvent::Change {
+            id: state.latest_id,
+            old_hash: old_hash.to_string(),
+            new_hash: stats.hash().to_string(),
+            stats: stats.clone(),
+        };
+        state.stats = stats;
+        if state.replay.len() == REPLAY_SIZE {
+            state.replay.pop_front();
+        }
+        state.replay.push_back(event.clone());
+        if let Some(sender) = &state.sender {
+            // Fails only if nobody is subscribed.
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Subscribes to the events after `last_event_id`, or to the future events
+    /// without an id.
+    pub(crate) fn subscribe(self: &Arc<Self>, last_event_id: Option<u64>) -> Subscription {
+        let state = self.state.lock().expect("events lock poisoned");
+        // Subscribed under the lock, so that no event is both replayed and received.
+        let receiver = state.sender.as_ref().map(broadcast::Sender::subscribe);
+        let pending = match last_event_id {
+            None => VecDeque::new(),
+            Some(last) if last == state.latest_id => VecDeque::new(),
+            Some(last) if last < state.latest_id && state.replayable(last) => state
+                .replay
+                .iter()
+                .filter(|event| event.id() > last)
+                .cloned()
+                .collect(),
+            Some(_) => VecDeque::from([state.resync()]),
+        };
+        Subscription {
+            pending,
+            receiver,
+            last_id: state.latest_id,
+            events: self.clone(),
+        }
+    }
+
+    /// Ends all subscriptions, so that open streams do not hold up the shutdown.
+    pub(crate) fn close(&self) {
+        self.state.lock().expect("events lock poisoned").sender = None;
+    }
+}
+
+impl State {
+    /// True if all events after `last` are still kept.
+    fn replayable(&self, last: u64) -> bool {
+        self.replay
+            .front()
+            .is_some_and(|oldest| oldest.id() <= last + 1)
+    }
+
+    fn resync(&self) -> DataEvent {
+        DataEvent::Resync {
+            id: self.latest_id,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+/// The events of one client: replayed ones first, then live ones.
+pub(crate) struct Subscription {
+    pending: VecDeque<DataEvent>,
+    receiver: Option<broadcast::Receiver<DataEvent>>,
+    last_id: u64,
+    /// Needed to resync a client that fell behind.
+    events: Arc<Events>,
+}
+
+impl Subscription {
+    /// The next event, or `None` once the events are closed.
+    pub(crate) async fn next(&mut self) -> Option<DataEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let receiver = self.receiver.as_mut()?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) if event.id() > self.last_id => {
+                    self.last_id = event.id();
+                    return Some(event);
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    debug!(missed, "Subscriber fell behind; resync");
+                    let resync = self
+                        .events
+                        .state
+                        .lock()
+                        .expect("events lock poisoned")
+                        .resync();
+                    self.last_id = resync.id();
+                    return Some(resync);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Extracts the id to subscribe after from the `Last-Event-ID` header or the
+/// `last_event_id` query parameter, if any.
+pub(crate) fn last_event_id()
+-> impl Filter<Extract = (Option<u64>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<u64>("last-event-id")
+        .and(warp::query::<EventsQuery>())
+        .map(|header: Option<u64>, query: EventsQuery| header.or(query.last_event_id()))
+}
+
+/// Streams the events as Server-Sent Events, with a comment line as heartbeat.
+pub(crate) fn sse_reply(subscription: Subscription, heartbeat: Duration) -> impl warp::Reply {
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        let sse = Event::default()
+            .id(event.id().to_string())
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_else(|_| Event::default().comment("unserializable event"));
+        Some((Ok::<_, Infallible>(sse), subscription))
+    });
+    warp::sse::reply(warp::sse::keep_alive().interval(heartbeat).stream(events))
+}
+
+/// Sends the events as JSON text messages, with a ping frame as heartbeat.
+pub(crate) async fn websocket(
+    socket: WebSocket,
+    mut subscription: Subscription,
+    heartbeat: Duration,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut heartbeats =
+        tokio::time::interval_at(tokio::time::Instant::now() + heartbeat, heartbeat);
+    loop {
+        let message = tokio::select! {
+            event = subscription.next() => match event {
+                Some(event) => match serde_json::to_string(&event) {
+                    Ok(json) => Message::text(json),
+                    Err(_) => continue,
+                },
+                None => {
+                    let _ = sender.send(Message::close()).await;
+                    return;
+                }
+            },
+            _ = heartbeats.tick() => Message::ping(Vec::new()),
+            incoming = receiver.next() => match incoming {
+                Some(Ok(message)) if !message.is_close() => continue,
+                _ => return,
+            },
+        };
+        if sender.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Reply;
+
+    fn stats(hash: &str) -> Stats {
+        Stats::new(String::new(), hash.to_string(), 0, 0, 0)
+    }
+
+    fn change_ids(events: &[DataEvent]) -> Vec<u64> {
+        events.iter().map(DataEvent::id).collect()
+    }
+
+    async fn drain(subscription: &mut Subscription, count: usize) -> Vec<DataEvent> {
+        let mut events = Vec::new();
+        for _ in 0..count {
+            events.push(subscription.next().await.unwrap());
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn live_events_reach_every_subscriber() {
+        let events = Arc::new(Events::new(stats("a")));
+        let mut first = events.subscribe(None);
+        let mut second = events.subscribe(None);
+
+        events.publish("a", stats("b"));
+
+        let expected = DataEvent::Change {
+            id: first.last_id + 1,
+            old_hash: String::from("a"),
+            new_hash: String::from("b"),
+            stats: stats("b"),
+        };
+        assert_eq!(first.next().await, Some(expected.clone()));
+        assert_eq!(second.next().await, Some(expected));
+    }
+
+    #[tokio::test]
+    async fn reconnect_replays_missed_events() {
+        let events = Arc::new(Events::new(stats("a")));
+        let mut live = events.subscribe(None);
+        events.publish("a", stats("b"));
+        events.publish("b", stats("c"));
+        events.publish("c", stats("d"));
+        let seen = drain(&mut live, 3).await;
+
+        let mut resumed = events.subscribe(Some(seen[0].id()));
+        events.publish("d", stats("e"));
+
+        let replayed = drain(&mut resumed, 3).await;
+        assert_eq!(
+            change_ids(&replayed),
+            [seen[1].id(), seen[2].id(), seen[2].id() + 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_or_expired_id_resyncs() {
+        let events = Arc::new(Events::new(stats("a")));
+        let first = events.subscribe(None).last_id;
+        for i in 0..=REPLAY_SIZE {
+            events.publish("x", stats(&i.to_string()));
+        }
+
+        for last in [first, first + 1_000] {
+            let mut subscription = events.subscribe(Some(last));
+            assert_eq!(
+                subscription.next().await,
+                Some(DataEvent::Resync {
+                    id: first + REPLAY_SIZE as u64 + 1,
+                    stats: stats(&REPLAY_SIZE.to_string()),
+                })
+            );
+        }
+        // The oldest kept event can still be resumed from its predecessor.
+        let mut subscription = events.subscribe(Some(first + 1));
+        assert_eq!(subscription.next().await.unwrap().id(), first + 2);
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_resyncs() {
+        let events = Arc::new(Events::new(stats("a")));
+        let mut slow = events.subscribe(None);
+        for i in 0..REPLAY_SIZE + 10 {
+            events.publish("x", stats(&i.to_string()));
+        }
+
+        let event = slow.next().await.unwrap();
+        assert_eq!(event.name(), "resync");
+        assert!(slow.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn close_ends_subscriptions() {
+        let events = Arc::new(Events::new(stats("a")));
+        let mut subscription = events.subscribe(None);
+
+        events.close();
+
+        assert_eq!(subscription.next().await, None);
+        assert_eq!(events.subscribe(None).next().await, None);
+    }
+
+    #[tokio::test]
+    async fn sse_stream_carries_id_and_name() {
+        let events = Arc::new(Events::new(stats("a")));
+        let filter = {
+            let events = events.clone();
+            last_event_id().map(move |id| sse_reply(events.subscribe(id), Duration::from_secs(60)))
+        };
+        let reply = warp::test::request()
+            .header("last-event-id", "1")
+            .filter(&filter)
+            .await
+            .unwrap();
+        events.close();
+
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("event:resync\n"));
+        assert!(body.contains(r#"data:{"type":"RESYNC""#));
+    }
+
+    #[tokio::test]
+    async fn websocket_sends_events_as_json() {
+        let events = Arc::new(Events::new(stats("a")));
+        let filter = {
+            let events = events.clone();
+            warp::ws()
+                .and(last_event_id())
+                .map(move |ws: warp::ws::Ws, id| {
+                    let subscription = events.subscribe(id);
+                    ws.on_upgrade(move |socket| {
+                        websocket(socket, subscription, Duration::from_secs(60))
+                    })
+                })
+        };
+        let mut client = warp::test::ws().handshake(filter).await.unwrap();
+
+        events.publish("a", stats("b"));
+
+        let message = client.recv().await.unwrap();
+        let event: DataEvent = serde_json::from_str(message.to_str().unwrap()).unwrap();
+        assert_eq!(event.name(), "change");
+        events.close();
+        client.recv_closed().await.unwrap();
+    }
+}
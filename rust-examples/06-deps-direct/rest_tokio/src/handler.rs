@@ -13,19 +13,660 @@
 // limitations under the License.
 
 
-use crate::types::health::Health;
-use crate::types::MetaDataStore;
-use warp;
+use crate::auth::Unauthorized;
+use crate::build_info;
+use crate::conditional;
+use crate::conditional::{Preconditions, Validators};
+use crate::diff;
+use crate::errors;
+use crate::errors::ApiError;
+use crate::errors::QueryError;
+use crate::events;
+use crate::listing;
+use crate::listing::{ListOptions, SortedOrders};
+use crate::logging::LogHandle;
+use crate::metrics::Metrics;
+use crate::registry::{Registered, Registry};
+use crate::reload::Reloader;
+use crate::response_cache::{CachedBody, Encoding, ResponseCache};
+use crate::routes;
+use crate::types::active_data_set::ActiveDataSet;
+use crate::types::content_hash;
+use crate::types::data_set::DataSet;
+use crate::types::diff_query::DiffQuery;
+use crate::types::health::{Health, HealthStatus};
+use crate::types::instrument_query::InstrumentQuery;
+use crate::types::list_query::ListQuery;
+use crate::types::log_filter::LogFilter;
+use crate::types::page::Page;
+use crate::types::problem::{ErrorCode, Problem};
+use crate::types::rollback_query::RollbackQuery;
+use crate::types::schedule_info::ScheduleInfo;
+use arc_swap::ArcSwap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use warp::Reply;
+use warp::http::header::{ALLOW, CONTENT_TYPE, HeaderValue, WWW_AUTHENTICATE};
+use warp::http::{Method, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::path::FullPath;
+use warp::ws::Ws;
 
 pub(crate) async fn get_health_handler() -> Result<impl warp::Reply, warp::Rejection> {
     let result = Health::ok();
     Ok(warp::reply::json(&result))
 }
 
+pub(crate) async fn get_version_handler() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&build_info::current()))
+}
+
+pub(crate) async fn get_ready_handler(
+    reloader: Arc<Reloader>,
+    max_staleness: Option<Duration>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // A pinned snapshot is meant to be served however old it gets.
+    let max_staleness = max_staleness.filter(|_| reloader.pinned().is_none());
+    let result = Health::readiness(&reloader.status(), reloader.clock().now(), max_staleness);
+    let status = match result.status() {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&result), status))
+}
+
+/// The data sets the read-only data routes answer from: the active one and,
+/// for the cursors of earlier pages, the snapshots kept in a history.
+pub(crate) trait DataSets: Send + Sync {
+    fn active(&self) -> &ArcSwap<ActiveDataSet>;
+
+    /// The snapshot with `hash`, if it is still kept.
+    fn snapshot(&self, _hash: &str) -> Option<Arc<DataSet>> {
+        None
+    }
+}
+
+/// A bare store keeps no history, so cursors of earlier data sets expire.
+impl DataSets for ArcSwap<ActiveDataSet> {
+    fn active(&self) -> &ArcSwap<ActiveDataSet> {
+        self
+    }
+}
+
+impl DataSets for Reloader {
+    fn active(&self) -> &ArcSwap<ActiveDataSet> {
+        self.store()
+    }
+
+    fn snapshot(&self, hash: &str) -> Option<Arc<DataSet>> {
+        Reloader::snapshot(self, hash)
+    }
+}
+
 pub(crate) async fn get_stats_handler(
-    store: MetaDataStore,
+    data_sets: Arc<dyn DataSets>,
+    preconditions: Preconditions,
+    encoding: Encoding,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let guard = data_sets.active().load();
+    conditional::negotiated_reply(&preconditions, &validators(&guard), || {
+        Ok(match guard.responses() {
+            Some(responses) => responses.stats().reply(encoding),
+            None => warp::reply::json(guard.stats()).into_response(),
+        })
+    })
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_stats_diff_handler(
+    reloader: Arc<Reloader>,
+    query: DiffQuery,
+    preconditions: Preconditions,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let active = reloader.store().load().data_set().clone();
+    let snapshot = |hash: &str| {
+        if hash == active.stats().hash() {
+            return Ok(active.clone());
+        }
+        reloader
+            .snapshot(hash)
+            .ok_or_else(|| ApiError::NotFound(format!("No snapshot with hash {}", hash)))
+    };
+
+    let to = match query.to() {
+        Some(hash) => snapshot(hash),
+        None => Ok(active.clone()),
+    }
+    .map_err(warp::reject::custom)?;
+    let from = match query.from() {
+        Some(hash) => snapshot(hash),
+        None => reloader
+            .previous(to.stats().hash())
+            .ok_or_else(|| ApiError::NotFound(format!("No snapshot before {}", to.stats().hash()))),
+    }
+    .map_err(warp::reject::custom)?;
+    // Both snapshots are immutable, so their hashes identify the diff.
+    let tag = format!("{}..{}", from.stats().hash(), to.stats().hash());
+    conditional::reply(&preconditions, &Validators::derived(&tag), || {
+        Ok(warp::reply::json(&diff::diff(&from, &to)))
+    })
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_datasets_handler(
+    registry: Arc<Registry>,
+    preconditions: Preconditions,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let list = registry.list();
+    let tag = content_hash::content_hash(&list)
+        .map(content_hash::to_hex)
+        .map_err(|e| warp::reject::custom(ApiError::Internal(e.to_string())))?;
+    conditional::reply(&preconditions, &Validators::derived(&tag), || {
+        Ok(warp::reply::json(&list))
+    })
+    .map_err(warp::reject::custom)
+}
+
+/// Resolves the `{name}` of the `/datasets/{name}/...` routes to that data
+/// set.
+pub(crate) async fn find_data_set(
+    name: String,
+    registry: Arc<Registry>,
+) -> Result<Arc<Registered>, warp::Rejection> {
+    match registry.get(&name) {
+        Some(registered) => Ok(registered),
+        None => Err(warp::reject::custom(ApiError::NotFound(format!(
+            "No data set named {}",
+            name
+        )))),
+    }
+}
+
+pub(crate) async fn get_assets_handler(
+    data_sets: Arc<dyn DataSets>,
+    query: ListQuery,
+    preconditions: Preconditions,
+    encoding: Encoding,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    list_reply(
+        &query,
+        &preconditions,
+        &*data_sets,
+        Some((ResponseCache::assets, encoding)),
+        |data_set, options, sorted| {
+            listing::page(data_set, "assets", data_set.assets(), options, sorted)
+        },
+    )
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_asset_handler(
+    data_sets: Arc<dyn DataSets>,
+    id: String,
+    preconditions: Preconditions,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let guard = data_sets.active().load();
+    let result = guard
+        .asset(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("No asset with id {}", id)))?;
+    conditional::reply(&preconditions, &validators(&guard), || {
+        Ok(warp::reply::json(result))
+    })
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_exchanges_handler(
+    data_sets: Arc<dyn DataSets>,
+    query: ListQuery,
+    preconditions: Preconditions,
+    encoding: Encoding,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    list_reply(
+        &query,
+        &preconditions,
+        &*data_sets,
+        Some((ResponseCache::exchanges, encoding)),
+        |data_set, options, sorted| {
+            listing::page(data_set, "exchanges", data_set.exchanges(), options, sorted)
+        },
+    )
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_exchange_handler(
+    data_sets: Arc<dyn DataSets>,
+    id: String,
+    preconditions: Preconditions,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let guard = data_sets.active().load();
+    let result = guard
+        .exchange(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("No exchange with id {}", id)))?;
+    conditional::reply(&preconditions, &validators(&guard), || {
+        Ok(warp::reply::json(result))
+    })
+    .map_err(warp::reject::custom)
+}
+
+pub(crate) async fn get_instruments_handler(
+    data_sets: Arc<dyn DataSets>,
+    filter: InstrumentQuery,
+    query: ListQuery,
+    preconditions: Preconditions,
+    encoding: Encoding,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let cached = match (filter.exchange(), filter.asset()) {
+        (None, None) => Some((ResponseCache::instruments as CachedPage, encoding)),
+        _ => None,
+    };
+    list_reply(
+        &query,
+        &preconditions,
+        &*data_sets,
+        cached,
+        |data_set, options, sorted| {
+            let instruments = data_set.instruments(filter.exchange(), filter.asset());
+            // The filtered lists are sorted apart from each other.
+            let list = format!("instruments {:?} {:?}", filter.exchange(), filter.asset());
+            listing::page(data_set, &list, &instruments, options, sorted)
+        },
+    )
+    .map_err(warp::reject::custom)
+}
+
+/// Validators of a response served from the active data set.
+fn validators(active: &ActiveDataSet) -> Validators {
+    Validators::new(active, Some(active.swapped_at()))
+}
+
+/// Selects the prepared first page of a list route.
+type CachedPage = fn(&ResponseCache) -> &CachedBody;
+
+/// Answers a list request with one page of the records selected by `page`.
+///
+/// A request without parameters is answered with the prepared first page from
+/// `cached`, if there is one. Without a cursor the active data set is listed.
+/// With a cursor, the data set the cursor was issued for is listed, if it is
+/// still in the history. Its swap time is only known while it is active, so
+/// otherwise the page carries no `Last-Modified`, and its sorted orders are
+/// not kept.
+fn list_reply<F>(
+    query: &ListQuery,
+    preconditions: &Preconditions,
+    data_sets: &dyn DataSets,
+    cached: Option<(CachedPage, Encoding)>,
+    page: F,
+) -> Result<warp::reply::Response, ApiError>
+where
+    F: FnOnce(&DataSet, &ListOptions, Option<&SortedOrders>) -> Result<Page, QueryError>,
+{
+    let options = ListOptions::parse(query)?;
+    let active = data_sets.active().load_full();
+    let (data_set, validators, sorted) = match options.cursor() {
+        Some(cursor) if cursor.hash() != active.stats().hash() => {
+            match data_sets.snapshot(cursor.hash()) {
+                Some(data_set) => {
+                    let validators = Validators::new(&data_set, None);
+                    (data_set, validators, None)
+                }
+                None => return Err(ApiError::CursorExpired(cursor.hash().to_string())),
+            }
+        }
+        _ => (
+            active.data_set().clone(),
+            validators(&active),
+            active.responses().map(ResponseCache::sorted),
+        ),
+    };
+    let prepared = match (cached, active.responses()) {
+        (Some((select, encoding)), Some(responses)) if *query == ListQuery::default() => {
+            Some(select(responses).reply(encoding))
+        }
+        _ => None,
+    };
+    conditional::negotiated_reply(preconditions, &validators, || match prepared {
+        Some(prepared) => Ok(prepared),
+        None => Ok(warp::reply::json(&page(&data_set, &options, sorted)?).into_response()),
+    })
+}
+
+pub(crate) async fn get_events_handler(
+    reloader: Arc<Reloader>,
+    last_event_id: Option<u64>,
+    heartbeat: Duration,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let subscription = reloader.events().subscribe(last_event_id);
+    Ok(events::sse_reply(subscription, heartbeat))
+}
+
+pub(crate) async fn get_ws_handler(
+    reloader: Arc<Reloader>,
+    ws: Ws,
+    last_event_id: Option<u64>,
+    heartbeat: Duration,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let subscription = reloader.events().subscribe(last_event_id);
+    Ok(ws.on_upgrade(move |socket| events::websocket(socket, subscription, heartbeat)))
+}
+
+pub(crate) async fn get_metrics_handler(
+    metrics: Arc<Metrics>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    match metrics.render() {
+        Ok(body) => Ok(warp::reply::with_header(
+            body,
+            "content-type",
+            "text/plain; version=0.0.4; charset=utf-8",
+        )
+        .into_response()),
+        Err(e) => Err(warp::reject::custom(ApiError::Internal(format!(
+            "Failed to render metrics: {}",
+            e
+        )))),
+    }
+}
+
+pub(crate) async fn get_log_filter_handler(
+    log: LogHandle,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let guard = store.load();
-    let result = guard.stats();
-    Ok(warp::reply::json(result))
+    let result = LogFilter::new(log.filter());
+    Ok(warp::reply::json(&result))
+}
+
+pub(crate) async fn put_log_filter_handler(
+    body: Bytes,
+    log: LogHandle,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let directives = String::from_utf8_lossy(&body);
+    match log.set_filter(&directives) {
+        Ok(()) => {
+            info!(filter = %log.filter(), "Log filter updated");
+            let result = LogFilter::new(log.filter());
+            Ok(warp::reply::json(&result).into_response())
+        }
+        Err(e) => {
+            warn!(error = %errors::report(&e), "Rejected log filter update");
+            Err(warp::reject::custom(ApiError::InvalidBody(errors::report(
+                &e,
+            ))))
+        }
+    }
+}
+
+pub(crate) async fn post_reload_handler(
+    reloader: Arc<Reloader>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if let Some(hash) = reloader.pinned() {
+        return Err(warp::reject::custom(ApiError::SnapshotPinned(hash)));
+    }
+    match reloader.reload().await {
+        Ok(result) => {
+            info!(
+                changed = result.changed(),
+                old_hash = result.old_hash(),
+                new_hash = result.new_hash(),
+                coalesced = result.coalesced(),
+                "On-demand reload finished"
+            );
+            Ok(warp::reply::json(&result).into_response())
+        }
+        Err(e) => Err(warp::reject::custom(ApiError::Reload(e))),
+    }
+}
+
+pub(crate) async fn get_snapshots_handler(
+    reloader: Arc<Reloader>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = reloader.snapshots();
+    Ok(warp::reply::json(&result))
+}
+
+pub(crate) async fn get_quarantine_handler(
+    reloader: Arc<Reloader>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = reloader.quarantined();
+    Ok(warp::reply::json(&result))
+}
+
+pub(crate) async fn post_rollback_handler(
+    reloader: Arc<Reloader>,
+    hash: String,
+    query: RollbackQuery,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    match reloader.rollback(&hash, query.pin()).await {
+        Some(result) => Ok(warp::reply::json(&result).into_response()),
+        None => Err(warp::reject::custom(ApiError::NotFound(format!(
+            "No snapshot with hash {}",
+            hash
+        )))),
+    }
+}
+
+pub(crate) async fn delete_pin_handler(
+    reloader: Arc<Reloader>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    reloader.unpin();
+    let result = reloader.snapshots();
+    Ok(warp::reply::json(&result))
+}
+
+/// Number of upcoming runs listed by `GET /admin/schedule`.
+const UPCOMING_RUNS: usize = 5;
+
+pub(crate) async fn get_schedule_handler(
+    data_set: Arc<Registered>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let schedule = data_set.schedule();
+    let next_runs = schedule
+        .upcoming(data_set.reloader().clock().now())
+        .take(UPCOMING_RUNS)
+        .map(|run| run.to_rfc3339())
+        .collect();
+    let result = ScheduleInfo::new(
+        schedule.expression().to_string(),
+        schedule.timezone().name().to_string(),
+        schedule.jitter().as_secs(),
+        next_runs,
+    );
+    Ok(warp::reply::json(&result))
+}
+
+/// Rejects requests outside of the route table with `404`, and requests with
+/// a method the route does not answer with `405`. Requests to a known route
+/// and method are left to the rejection of that route.
+pub(crate) async fn fallback_handler(
+    path: FullPath,
+    method: Method,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    match routes::find(path.as_str()) {
+        None => Err(warp::reject::custom(ApiError::NotFound(format!(
+            "No route for {}",
+            path.as_str()
+        )))),
+        Some(route) if !route.methods.contains(&method) => Err(warp::reject::custom(
+            ApiError::MethodNotAllowed(route.methods),
+        )),
+        Some(_) => Err(warp::reject::not_found()),
+    }
+}
+
+/// Answers every rejection with an `application/problem+json` body.
+pub(crate) async fn handle_rejection(
+    rejection: warp::Rejection,
+) -> Result<warp::reply::Response, Infallible> {
+    if let Some(e) = rejection.find::<ApiError>() {
+        if e.status().is_server_error() {
+            error!(error = %errors::report(e), "Request failed");
+        }
+        let mut response = problem(e.code(), e.status(), Some(e.detail()));
+        if let ApiError::MethodNotAllowed(methods) = e {
+            let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+            if let Ok(allow) = HeaderValue::from_str(&methods.join(", ")) {
+                response.headers_mut().insert(ALLOW, allow);
+            }
+        }
+        return Ok(response);
+    }
+    if rejection.find::<Unauthorized>().is_some() {
+        let mut response = problem(
+            ErrorCode::Unauthorized,
+            StatusCode::UNAUTHORIZED,
+            Some(String::from("Missing or invalid bearer token")),
+        );
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return Ok(response);
+    }
+
+    let (code, status, detail) = if let Some(e) = rejection.find::<warp::reject::InvalidQuery>() {
+        (
+            ErrorCode::InvalidQuery,
+            StatusCode::BAD_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::reject::MissingHeader>() {
+        (
+            ErrorCode::InvalidHeader,
+            StatusCode::BAD_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::reject::InvalidHeader>() {
+        (
+            ErrorCode::InvalidHeader,
+            StatusCode::BAD_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::reject::PayloadTooLarge>() {
+        (
+            ErrorCode::PayloadTooLarge,
+            StatusCode::PAYLOAD_TOO_LARGE,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::reject::LengthRequired>() {
+        (
+            ErrorCode::InvalidBody,
+            StatusCode::LENGTH_REQUIRED,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::reject::MethodNotAllowed>() {
+        (
+            ErrorCode::MethodNotAllowed,
+            StatusCode::METHOD_NOT_ALLOWED,
+            e.to_string(),
+        )
+    } else if rejection.is_not_found() {
+        (
+            ErrorCode::NotFound,
+            StatusCode::NOT_FOUND,
+            String::from("Not found"),
+        )
+    } else {
+        error!(?rejection, "Unhandled rejection");
+        (
+            ErrorCode::Internal,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            String::from("Internal error"),
+        )
+    };
+    Ok(problem(code, status, Some(detail)))
+}
+
+fn problem(code: ErrorCode, status: StatusCode, detail: Option<String>) -> warp::reply::Response {
+    let result = Problem::new(
+        code,
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Error").to_string(),
+        detail,
+    );
+    let mut response = warp::reply::with_status(warp::reply::json(&result), status).into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/problem+json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LoadError;
+    use std::path::PathBuf;
+    use warp::Filter;
+    use warp::hyper::body;
+
+    async fn problem_of(rejection: warp::Rejection) -> (warp::reply::Response, serde_json::Value) {
+        let response = handle_rejection(rejection).await.unwrap();
+        let (parts, bytes) = response.into_parts();
+        let bytes = body::to_bytes(bytes).await.unwrap();
+        let response = warp::reply::Response::from_parts(parts, body::Body::empty());
+        (response, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn fallback(path: &str, method: Method) -> warp::Rejection {
+        warp::test::request()
+            .path(path)
+            .method(method.as_str())
+            .filter(
+                &warp::path::full()
+                    .and(warp::method())
+                    .and_then(fallback_handler),
+            )
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_a_json_not_found() {
+        let (response, body) = problem_of(fallback("/nope", Method::GET).await).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        assert_eq!(body["code"], "NOT_FOUND");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["type"], "urn:rest-tokio:problem:not-found");
+    }
+
+    #[tokio::test]
+    async fn wrong_method_lists_the_allowed_methods() {
+        let (response, body) = problem_of(fallback("/admin/log-level", Method::DELETE).await).await;
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, PUT");
+        assert_eq!(body["code"], "METHOD_NOT_ALLOWED");
+    }
+
+    #[tokio::test]
+    async fn known_route_defers_to_the_route_rejection() {
+        assert!(fallback("/assets/BTC", Method::GET).await.is_not_found());
+    }
+
+    #[tokio::test]
+    async fn source_errors_keep_their_code_and_cause() {
+        let error = LoadError::Io {
+            path: PathBuf::from("/missing.json"),
+            source: Arc::new(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        };
+        let (response, body) = problem_of(warp::reject::custom(ApiError::Reload(error))).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(body["code"], "SOURCE_IO");
+        assert!(
+            body["detail"]
+                .as_str()
+                .unwrap()
+                .contains("entity not found")
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_asks_for_a_bearer_token() {
+        let (response, body) = problem_of(warp::reject::custom(Unauthorized)).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+        assert_eq!(body["code"], "UNAUTHORIZED");
+    }
 }
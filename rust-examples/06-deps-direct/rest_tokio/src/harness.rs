@@ -0,0 +1,619 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! In-process harness for the route tests: starts the service on a fake data
+//! source and serves its routes with `warp::test` or on an ephemeral port.
+//!
+//! The service runs on a fake clock, so that tests move the reload schedule
+//! and the age of the data forward without waiting.
+
+use crate::clock::{Clock, FakeClock};
+use crate::config::{AccessLogFormat, Config};
+use crate::logging::LogHandle;
+use crate::routes::build_service_routes;
+use crate::service::Service;
+use crate::source::{DataSource, FakeSource};
+use crate::types::data_event::DataEvent;
+use chrono::{DateTime, TimeZone, Utc};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use warp::http::Response;
+use warp::hyper::body::Bytes;
+
+/// Two versions of the data, told apart by their asset.
+pub(crate) const DATA_V1: &str =
+    r#"{"assets": [{"id": "BTC"}], "exchanges": [{"id": "KRAKEN"}], "instruments": []}"#;
+pub(crate) const DATA_V2: &str = r#"{"assets": [{"id": "BTC"}, {"id": "ETH"}], "exchanges": [{"id": "KRAKEN"}], "instruments": []}"#;
+
+/// A started service on a fake data source.
+pub(crate) struct Harness {
+    service: Service,
+    source: FakeSource,
+    clock: Arc<FakeClock>,
+}
+
+impl Harness {
+    /// Starts the service with the default config on a source serving `json`.
+    pub(crate) async fn start(json: &str) -> Self {
+        Self::with_config(json, Config::default()).await
+    }
+
+    /// Starts the service with `config` on a source serving `json`. The
+    /// access log is off to keep the test output readable.
+    pub(crate) async fn with_config(json: &str, mut config: Config) -> Self {
+        config.log.access = AccessLogFormat::Off;
+        let source = FakeSource::new(json);
+        let clock = Arc::new(FakeClock::new(start()));
+        let service = Service::with_source(
+            &config,
+            LogHandle::detached("off"),
+            DataSource::Fake(source.clone()),
+            clock.clone(),
+        )
+        .await;
+        Self {
+            service,
+            source,
+            clock,
+        }
+    }
+
+    /// The data source; changes to it are picked up by the next reload.
+    pub(crate) fn source(&self) -> &FakeSource {
+        &self.source
+    }
+
+    /// The clock of the service. It stands still unless a test moves it.
+    pub(crate) fn clock(&self) -> &FakeClock {
+        &self.clock
+    }
+
+    /// Sends `request` through the routes without a network connection.
+    pub(crate) async fn reply(&self, request: warp::test::RequestBuilder) -> Response<Bytes> {
+        request.reply(&build_service_routes(&self.service)).await
+    }
+
+    /// Answers `GET path`.
+    pub(crate) async fn get(&self, path: &str) -> Response<Bytes> {
+        self.reply(warp::test::request().path(path)).await
+    }
+
+    /// Sets the clock to the next scheduled reload, by default at 01:00 UTC,
+    /// and waits until that reload has been recorded. Returns the change
+    /// event if the reload swapped in new data.
+    pub(crate) async fn run_scheduled_reload(&self) -> Option<DataEvent> {
+        let default = self.service.registry.default_data_set();
+        let reloader = default.reloader();
+        let mut events = reloader.events().subscribe(None);
+        let mut status = reloader.watch_status();
+        let before = reloader.store().load().stats().hash().to_string();
+
+        let next = default
+            .schedule()
+            .upcoming(self.clock.now())
+            .next()
+            .expect("a scheduled reload");
+        self.clock.set(next.with_timezone(&Utc));
+        status.changed().await.expect("reloader running");
+
+        // A swap is announced before the reload is recorded.
+        if reloader.store().load().stats().hash() != before {
+            events.next().await
+        } else {
+            None
+        }
+    }
+
+    /// Serves the routes on an ephemeral port of the loopback interface until
+    /// the returned sender is dropped.
+    pub(crate) fn serve(&self) -> (SocketAddr, oneshot::Sender<()>) {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (addr, server) = warp::serve(build_service_routes(&self.service))
+            .bind_with_graceful_shutdown(([127, 0, 0, 1], 0), async move {
+                let _ = stopped.await;
+            });
+        tokio::spawn(server);
+        (addr, stop)
+    }
+
+    pub(crate) async fn shutdown(self) {
+        self.service.shutdown().await;
+    }
+}
+
+/// Time at which the fake clock of every harness starts.
+pub(crate) fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()
+}
+
+/// Decodes a JSON response body.
+pub(crate) fn json(response: &Response<Bytes>) -> serde_json::Value {
+    serde_json::from_slice(response.body()).expect("JSON body")
+}
+
+/// Reads a response header as a string.
+pub(crate) fn header<'a>(response: &'a Response<Bytes>, name: &str) -> Option<&'a str> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().expect("ASCII header"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DataSetConfig;
+    use crate::routes::build_routes;
+    use crate::types::MetaDataStore;
+    use crate::types::active_data_set::ActiveDataSet;
+    use crate::types::data_set::DataSet;
+    use arc_swap::ArcSwap;
+    use std::time::Duration;
+    use warp::http::StatusCode;
+
+    #[tokio::test]
+    async fn health_answers_ok() {
+        let harness = Harness::start(DATA_V1).await;
+
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .path("/health")
+                    .header("x-request-id", "health-1"),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "content-type"), Some("application/json"));
+        assert_eq!(header(&response, "x-request-id"), Some("health-1"));
+        assert_eq!(json(&response), serde_json::json!({"status": "OK"}));
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn version_describes_the_build() {
+        let harness = Harness::start(DATA_V1).await;
+
+        let response = harness.get("/version").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "content-type"), Some("application/json"));
+        let version = json(&response);
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        for field in ["git_commit", "build_timestamp", "rustc_version", "profile"] {
+            assert!(version[field].is_string(), "{}", field);
+        }
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn stats_describe_the_loaded_data() {
+        let harness = Harness::start(DATA_V1).await;
+
+        let response = harness.get("/stats").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats = json(&response);
+        assert_eq!(stats["number_assets"], 1);
+        assert_eq!(stats["number_exchanges"], 1);
+        assert_eq!(stats["number_instruments"], 0);
+        let hash = stats["hash"].as_str().unwrap();
+        assert_eq!(
+            header(&response, "etag"),
+            Some(format!("W/\"{}\"", hash).as_str())
+        );
+        assert!(header(&response, "last-modified").is_some());
+        assert!(header(&response, "x-request-id").is_some_and(|id| id.len() == 32));
+
+        let not_modified = harness
+            .reply(
+                warp::test::request()
+                    .path("/stats")
+                    .header("if-none-match", format!("W/\"{}\"", hash)),
+            )
+            .await;
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert!(not_modified.body().is_empty());
+        assert_eq!(header(&not_modified, "vary"), Some("accept-encoding"));
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn store_routes_serve_the_data_without_a_service() {
+        let active = |json: &str| {
+            let payload = serde_json::from_str(json).unwrap();
+            let data_set = DataSet::from_payload(payload, start()).unwrap();
+            ActiveDataSet::new(Arc::new(data_set), start())
+        };
+        let store: MetaDataStore = Arc::new(ArcSwap::from_pointee(active(DATA_V2)));
+        let routes = build_routes(store.clone());
+        let get = |path: &str| warp::test::request().path(path).reply(&routes);
+
+        let stats = get("/stats").await;
+        assert_eq!(stats.status(), StatusCode::OK);
+        assert_eq!(json(&stats)["number_assets"], 2);
+        assert_eq!(get("/assets/ETH").await.status(), StatusCode::OK);
+        assert_eq!(get("/ready").await.status(), StatusCode::NOT_FOUND);
+
+        let first = json(&get("/assets?limit=1").await);
+        let cursor = first["next_cursor"].as_str().unwrap().to_string();
+        store.store(Arc::new(active(DATA_V1)));
+        let expired = get(&format!("/assets?limit=1&cursor={}", cursor)).await;
+        assert_eq!(expired.status(), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn scheduled_reload_swaps_in_new_data() {
+        let harness = Harness::start(DATA_V1).await;
+        let before = json(&harness.get("/stats").await);
+        let old_etag = format!("W/\"{}\"", before["hash"].as_str().unwrap());
+
+        harness.source().set(DATA_V2);
+        let event = harness.run_scheduled_reload().await;
+
+        let after = json(&harness.get("/stats").await);
+        assert_eq!(after["number_assets"], 2);
+        assert_ne!(after["hash"], before["hash"]);
+        let event = serde_json::to_value(event.expect("change event")).unwrap();
+        assert_eq!(event["type"], "CHANGE");
+        assert_eq!(event["old_hash"], before["hash"]);
+        assert_eq!(event["new_hash"], after["hash"]);
+
+        // A client holding the old version is sent the new one.
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .path("/stats")
+                    .header("if-none-match", &old_etag),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(header(&response, "etag"), Some(old_etag.as_str()));
+
+        // Unchanged data is not swapped again.
+        assert!(harness.run_scheduled_reload().await.is_none());
+
+        // A failed reload keeps the last good data.
+        harness.source().fail("connection refused");
+        assert!(harness.run_scheduled_reload().await.is_none());
+        assert_eq!(json(&harness.get("/stats").await), after);
+        assert_eq!(harness.get("/ready").await.status(), StatusCode::OK);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn stats_diff_is_not_modified_for_the_same_snapshots() {
+        let harness = Harness::start(DATA_V1).await;
+        harness.source().set(DATA_V2);
+        harness.run_scheduled_reload().await.expect("change event");
+
+        let response = harness.get("/stats/diff").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let diff = json(&response);
+        let etag = format!(
+            "W/\"{}..{}\"",
+            diff["from"].as_str().unwrap(),
+            diff["to"].as_str().unwrap()
+        );
+        assert_eq!(header(&response, "etag"), Some(etag.as_str()));
+
+        let not_modified = harness
+            .reply(
+                warp::test::request()
+                    .path("/stats/diff")
+                    .header("if-none-match", &etag),
+            )
+            .await;
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert!(not_modified.body().is_empty());
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn datasets_are_not_modified_until_a_data_set_changes() {
+        let harness = Harness::start(DATA_V1).await;
+        let response = harness.get("/datasets").await;
+        let etag = header(&response, "etag").expect("etag").to_string();
+
+        let not_modified = harness
+            .reply(
+                warp::test::request()
+                    .path("/datasets")
+                    .header("if-none-match", &etag),
+            )
+            .await;
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+
+        harness.source().set(DATA_V2);
+        harness.run_scheduled_reload().await.expect("change event");
+        let changed = harness
+            .reply(
+                warp::test::request()
+                    .path("/datasets")
+                    .header("if-none-match", &etag),
+            )
+            .await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(header(&changed, "etag"), Some(etag.as_str()));
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn service_becomes_ready_once_the_source_recovers() {
+        let harness = Harness::start("not json").await;
+
+        // The initial load failed to decode, so the service has no data yet.
+        let response = harness.get("/ready").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json(&response)["reasons"][0]["code"], "NO_DATA");
+
+        harness.source().set(DATA_V1);
+        assert!(harness.run_scheduled_reload().await.is_some());
+
+        let response = harness.get("/ready").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(&response)["status"], "OK");
+        assert_eq!(json(&harness.get("/stats").await)["number_assets"], 1);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn data_older_than_max_staleness_is_not_ready() {
+        let harness = Harness::start(DATA_V1).await;
+        harness.source().fail("connection refused");
+
+        // The daily reload fails, but the data is still within 26 hours.
+        assert!(harness.run_scheduled_reload().await.is_none());
+        assert_eq!(harness.get("/ready").await.status(), StatusCode::OK);
+
+        harness
+            .clock()
+            .set(start() + Duration::from_secs(26 * 60 * 60 + 1));
+        let response = harness.get("/ready").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json(&response)["reasons"][0]["code"], "STALE");
+        assert_eq!(json(&response)["last_success"], "2024-01-01T12:00:00Z");
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn pinned_snapshot_stays_ready_past_max_staleness() {
+        let mut config = Config::default();
+        config.admin.token = Some(String::from("s3cret"));
+        let harness = Harness::with_config(DATA_V1, config).await;
+        let hash = json(&harness.get("/stats").await)["hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        harness.source().set(DATA_V2);
+        assert!(harness.run_scheduled_reload().await.is_some());
+
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .method("POST")
+                    .path(&format!("/admin/rollback/{}?pin=true", hash))
+                    .header("authorization", "Bearer s3cret"),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        harness
+            .clock()
+            .set(start() + Duration::from_secs(7 * 24 * 60 * 60));
+        assert_eq!(harness.get("/ready").await.status(), StatusCode::OK);
+
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .method("DELETE")
+                    .path("/admin/pin")
+                    .header("authorization", "Bearer s3cret"),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = harness.get("/ready").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json(&response)["reasons"][0]["code"], "STALE");
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn named_data_set_answers_its_own_routes() {
+        let mut config = Config::default();
+        config.admin.token = Some(String::from("s3cret"));
+        config.datasets.insert(
+            String::from("rates"),
+            DataSetConfig {
+                source: String::from("builtin"),
+                schedule: None,
+                timezone: None,
+                validation: None,
+            },
+        );
+        let harness = Harness::with_config(DATA_V1, config).await;
+
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .method("POST")
+                    .path("/admin/datasets/rates/reload")
+                    .header("authorization", "Bearer s3cret"),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            harness.get("/datasets/rates/ready").await.status(),
+            StatusCode::OK
+        );
+        let assets = json(&harness.get("/datasets/rates/assets?limit=1").await);
+        let id = assets["items"][0]["id"].as_str().unwrap();
+        assert_ne!(id, "BTC");
+        let asset = harness.get(&format!("/datasets/rates/assets/{}", id)).await;
+        assert_eq!(asset.status(), StatusCode::OK);
+        assert_ne!(
+            json(&harness.get("/datasets/rates/stats").await)["hash"],
+            json(&harness.get("/stats").await)["hash"]
+        );
+        let unknown = harness.get("/datasets/venues/assets").await;
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn named_data_set_streams_its_events_and_has_its_own_admin_routes() {
+        let mut config = Config::default();
+        config.admin.token = Some(String::from("s3cret"));
+        config.datasets.insert(
+            String::from("rates"),
+            DataSetConfig {
+                source: String::from("builtin"),
+                schedule: Some(String::from("0 */15 * * * *")),
+                timezone: None,
+                validation: None,
+            },
+        );
+        let harness = Harness::with_config(DATA_V1, config).await;
+        let admin = |method: &str, path: &str| {
+            warp::test::request()
+                .method(method)
+                .path(path)
+                .header("authorization", "Bearer s3cret")
+        };
+        let reloaded = harness
+            .reply(admin("POST", "/admin/datasets/rates/reload"))
+            .await;
+        let hash = json(&reloaded)["new_hash"].clone();
+        assert!(hash.is_string());
+
+        let snapshots = harness
+            .reply(admin("GET", "/admin/datasets/rates/snapshots"))
+            .await;
+        assert_eq!(snapshots.status(), StatusCode::OK);
+        assert_eq!(json(&snapshots)["snapshots"][0]["hash"], hash);
+        let schedule = harness
+            .reply(admin("GET", "/admin/datasets/rates/schedule"))
+            .await;
+        assert_eq!(json(&schedule)["schedule"], "0 */15 * * * *");
+        let rollback = harness
+            .reply(admin(
+                "POST",
+                "/admin/datasets/rates/rollback/0000000000000000",
+            ))
+            .await;
+        assert_eq!(rollback.status(), StatusCode::NOT_FOUND);
+        let quarantine = harness
+            .reply(admin("GET", "/admin/datasets/rates/quarantine"))
+            .await;
+        assert_eq!(quarantine.status(), StatusCode::OK);
+        let unauthorized = harness.get("/admin/datasets/rates/snapshots").await;
+        assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+
+        // An unknown event id is answered with the current stats of the data set.
+        let mut client = warp::test::ws()
+            .path("/datasets/rates/ws?last_event_id=0")
+            .handshake(build_service_routes(&harness.service))
+            .await
+            .unwrap();
+        let message = client.recv().await.unwrap();
+        let event: serde_json::Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+        assert_eq!(event["type"], "RESYNC");
+        assert_eq!(event["stats"]["hash"], hash);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn truncated_data_is_quarantined_instead_of_served() {
+        let mut config = Config::default();
+        config.admin.token = Some(String::from("s3cret"));
+        let harness = Harness::with_config(DATA_V2, config).await;
+
+        harness
+            .source()
+            .set(r#"{"assets": [], "exchanges": [{"id": "KRAKEN"}]}"#);
+        assert!(harness.run_scheduled_reload().await.is_none());
+
+        let response = harness.get("/ready").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(&harness.get("/stats").await)["number_assets"], 2);
+
+        let response = harness
+            .reply(
+                warp::test::request()
+                    .path("/admin/quarantine")
+                    .header("authorization", "Bearer s3cret"),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let rejected = &json(&response)["rejected"][0];
+        assert_eq!(rejected["number_assets"], 0);
+        assert_eq!(
+            rejected["violations"],
+            serde_json::json!(["assets: dropped from 2 to 0 records (100%), allowed are 50%"])
+        );
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn server_on_an_ephemeral_port_answers_over_http() {
+        let harness = Harness::start(DATA_V1).await;
+        let (addr, _stop) = harness.serve();
+        let client = reqwest::Client::new();
+
+        let health = client
+            .get(format!("http://{}/health", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(health.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&health.bytes().await.unwrap()).unwrap(),
+            serde_json::json!({"status": "OK"})
+        );
+
+        let stats = client
+            .get(format!("http://{}/stats", addr))
+            .header("x-request-id", "stats-1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stats.status(), reqwest::StatusCode::OK);
+        let headers = stats.headers().clone();
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["vary"], "accept-encoding");
+        assert_eq!(headers["x-request-id"], "stats-1");
+        let body: serde_json::Value =
+            serde_json::from_slice(&stats.bytes().await.unwrap()).unwrap();
+        assert_eq!(body["number_assets"], 1);
+        assert_eq!(
+            headers["etag"],
+            format!("W/\"{}\"", body["hash"].as_str().unwrap()).as_str()
+        );
+
+        let missing = client
+            .get(format!("http://{}/assets/DOGE", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(
+            missing.headers()["content-type"],
+            "application/problem+json"
+        );
+        harness.shutdown().await;
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! The sample REST service that serves reference data, as a library.
+//!
+//! [`main`] is the whole service as started by the binaries of this example
+//! and of the container example. Tests and other hosts start a [`Service`] and
+//! serve the routes of [`build_service_routes`] themselves, i.e. with
+//! `warp::test`. Hosts that swap in data sets on their own serve the read-only
+//! data routes of [`build_routes`] over a [`MetaDataStore`].
+
+use clap::Parser;
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+use warp::Filter;
+
+use crate::registry::Registry;
+
+mod access_log;
+mod auth;
+mod build_info;
+mod cache;
+mod clock;
+mod conditional;
+mod config;
+mod diff;
+mod errors;
+mod events;
+mod handler;
+#[cfg(test)]
+mod harness;
+mod listing;
+mod logging;
+mod metrics;
+mod notify;
+mod registry;
+mod reload;
+mod response_cache;
+mod routes;
+mod schedule;
+mod service;
+mod shutdown_utils;
+mod source;
+mod types;
+mod validate;
+
+pub use crate::config::Config;
+pub use crate::config::cli::Cli;
+pub use crate::logging::LogHandle;
+pub use crate::routes::build_routes;
+pub use crate::routes::build_service_routes;
+pub use crate::service::Service;
+pub use crate::types::MetaDataStore;
+pub use crate::types::active_data_set::ActiveDataSet;
+pub use crate::types::data_set::DataSet;
+pub use crate::types::payload::Payload;
+
+/// Parses the command line and runs the service on a multi-threaded runtime
+/// until it is shut down by a signal. This is all the `main` of a binary does.
+pub fn main() {
+    let cli = Cli::parse();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build runtime")
+        .block_on(run(cli));
+}
+
+/// Runs the service with the given flags until it is shut down by a signal.
+/// Exits the process on invalid configuration or if the port is taken.
+pub async fn run(cli: Cli) {
+    let config = match Config::load(&cli, env::vars()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("[main]: {}", errors::report(&e));
+            process::exit(2);
+        }
+    };
+
+    if cli.print_config {
+        match config.redacted().to_toml() {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("[main]: {}", errors::report(&e));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let log = match logging::init(&config.log) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("[main]: {}", errors::report(&e));
+            process::exit(1);
+        }
+    };
+
+    let (service, routes) = startup(&config, log)
+        .instrument(info_span!("startup"))
+        .await;
+
+    let addr = SocketAddr::new(config.server.bind_address, config.server.port);
+    let (stop_server, server_stopped) = oneshot::channel::<()>();
+    let (addr, server) =
+        match warp::serve(routes).try_bind_with_graceful_shutdown(addr, async move {
+            let _ = server_stopped.await;
+        }) {
+            Ok(bound) => bound,
+            Err(e) => {
+                error!(error = %e, %addr, "Failed to bind server");
+                process::exit(1);
+            }
+        };
+    let build = build_info::current();
+    info!(
+        service = "Sample Service",
+        version = build.version(),
+        commit = build.git_commit(),
+        built = build.build_timestamp(),
+        rustc = build.rustc_version(),
+        profile = build.profile(),
+        %addr,
+        "Service listening"
+    );
+    let mut server = tokio::spawn(server);
+
+    #[cfg(unix)]
+    tokio::spawn(reload_data_sets_on_hangup(
+        cli.clone(),
+        config.clone(),
+        service.registry.clone(),
+    ));
+
+    tokio::select! {
+        _ = &mut server => {
+            error!("Server stopped unexpectedly");
+            process::exit(1);
+        }
+        _ = shutdown_utils::signal_handler("Sample Service") => {}
+    }
+
+    shutdown(
+        config.server.shutdown_timeout(),
+        stop_server,
+        server,
+        service,
+    )
+    .instrument(info_span!("shutdown"))
+    .await;
+}
+
+/// Starts the service and builds its routes.
+async fn startup(
+    config: &Config,
+    log: LogHandle,
+) -> (
+    Service,
+    impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone + Send + Sync + 'static,
+) {
+    let start = Instant::now();
+    let service = Service::start(config, log).await;
+    let routes = build_service_routes(&service);
+    info!(elapsed = ?start.elapsed(), "Startup complete");
+    (service, routes)
+}
+
+/// Stops accepting connections and scheduling reloads, then waits until
+/// in-flight requests and running reloads have finished or the timeout passed.
+/// Event streams are ended right away.
+async fn shutdown(
+    timeout: Duration,
+    stop_server: oneshot::Sender<()>,
+    server: JoinHandle<()>,
+    service: Service,
+) {
+    let deadline = Instant::now() + timeout;
+    info!(?timeout, "Shutting down");
+
+    let _ = stop_server.send(());
+
+    let drain = async {
+        tokio::join!(
+            async {
+                service.shutdown().await;
+                debug!("Schedulers stopped");
+            },
+            async {
+                let _ = server.await;
+                debug!("Connections drained");
+            },
+        )
+    };
+    match tokio::time::timeout_at(deadline, drain).await {
+        Ok(_) => info!("Shutdown complete"),
+        Err(_) => warn!("Shutdown timed out; dropping remaining requests and reloads"),
+    }
+}
+
+/// Applies the `[datasets]` section of the config again on every `SIGHUP`.
+/// Changes to any other setting need a restart and are only reported.
+#[cfg(unix)]
+async fn reload_data_sets_on_hangup(cli: Cli, mut running: Config, registry: Arc<Registry>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!(error = %e, "Failed to listen for SIGHUP; data sets are fixed");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP; reloading data sets from the config");
+        let config = match Config::load(&cli, env::vars()) {
+            Ok(config) => config,
+            Err(e) => {
+                error!(error = %errors::report(&e), "Invalid config; keeping the running data sets");
+                continue;
+            }
+        };
+        let others_changed = Config {
+            datasets: running.datasets.clone(),
+            ..config.clone()
+        } != running;
+        if others_changed {
+            warn!("Only [datasets] is applied on SIGHUP; restart to apply the other changes");
+        }
+        registry
+            .apply(&config)
+            .instrument(info_span!("datasets"))
+            .await;
+        running.datasets = config.datasets;
+    }
+}
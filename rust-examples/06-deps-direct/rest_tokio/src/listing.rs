@@ -0,0 +1,498 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::QueryError;
+use crate::types::data_set::DataSet;
+use crate::types::list_query::ListQuery;
+use crate::types::page::Page;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Page size if the request does not set `limit`.
+const DEFAULT_LIMIT: usize = 100;
+/// Largest `limit` a request may set.
+const MAX_LIMIT: usize = 1000;
+/// Largest number of sorted orders kept per data set. The sort keys come from
+/// requests, so further orders are sorted again for every page.
+const MAX_SORTED_ORDERS: usize = 64;
+
+/// Position in a list of one data set.
+///
+/// A cursor names the data set by its hash. Paging through a list therefore
+/// continues on the same data set after a reload has swapped in new data,
+/// for as long as the data set is kept in the snapshot history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cursor {
+    hash: String,
+    offset: usize,
+}
+
+impl Cursor {
+    fn parse(cursor: &str) -> Result<Self, QueryError> {
+        let invalid = || QueryError::Cursor(cursor.to_string());
+        let (hash, offset) = cursor.split_once('.').ok_or_else(invalid)?;
+        if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let offset = offset.parse().map_err(|_| invalid())?;
+        Ok(Self {
+            hash: hash.to_string(),
+            offset,
+        })
+    }
+
+    pub(crate) fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.hash, self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SortKey {
+    field: String,
+    descending: bool,
+}
+
+/// Checked parameters of a list request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ListOptions {
+    limit: usize,
+    cursor: Option<Cursor>,
+    sort: Vec<SortKey>,
+    fields: Option<Vec<String>>,
+}
+
+impl ListOptions {
+    pub(crate) fn parse(query: &ListQuery) -> Result<Self, QueryError> {
+        let limit = match query.limit() {
+            None => DEFAULT_LIMIT,
+            Some(limit) => match limit.parse() {
+                Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+                _ => {
+                    return Err(QueryError::Limit {
+                        value: limit.to_string(),
+                        max: MAX_LIMIT,
+                    });
+                }
+            },
+        };
+        let cursor = query.cursor().map(Cursor::parse).transpose()?;
+        let sort = match query.sort() {
+            None => Vec::new(),
+            Some(sort) => field_list("sort", sort)?
+                .into_iter()
+                .map(|field| match field.strip_prefix('-') {
+                    Some(field) => check_field("sort", field).map(|field| SortKey {
+                        field,
+                        descending: true,
+                    }),
+                    None => check_field("sort", field).map(|field| SortKey {
+                        field,
+                        descending: false,
+                    }),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        let fields = match query.fields() {
+            None => None,
+            Some(fields) => Some(
+                field_list("fields", fields)?
+                    .into_iter()
+                    .map(|field| check_field("fields", field))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        Ok(Self {
+            limit,
+            cursor,
+            sort,
+            fields,
+        })
+    }
+
+    pub(crate) fn cursor(&self) -> Option<&Cursor> {
+        self.cursor.as_ref()
+    }
+}
+
+fn field_list<'a>(parameter: &'static str, list: &'a str) -> Result<Vec<&'a str>, QueryError> {
+    let fields: Vec<&str> = list.split(',').map(str::trim).collect();
+    if fields.iter().any(|field| field.is_empty()) {
+        return Err(QueryError::FieldList {
+            parameter,
+            list: list.to_string(),
+        });
+    }
+    Ok(fields)
+}
+
+fn check_field(parameter: &'static str, field: &str) -> Result<String, QueryError> {
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(QueryError::Field {
+            parameter,
+            field: field.to_string(),
+        });
+    }
+    Ok(field.to_string())
+}
+
+/// Sorted orders of the lists of one data set, so that paging through a
+/// sorted list sorts it only once. An order holds the positions of the
+/// records of a list, sorted by the sort keys of a request.
+#[derive(Debug, Default)]
+pub(crate) struct SortedOrders {
+    orders: Mutex<HashMap<OrderKey, Arc<[usize]>>>,
+}
+
+/// Name of a list and the sort keys of its order.
+type OrderKey = (String, Vec<SortKey>);
+
+impl SortedOrders {
+    /// The order of `records` of the list named `list` sorted by `sort`,
+    /// sorted on the first request.
+    fn get_or_sort<T: Serialize>(
+        &self,
+        list: &str,
+        sort: &[SortKey],
+        records: &[T],
+    ) -> Result<Arc<[usize]>, QueryError> {
+        let key = (list.to_string(), sort.to_vec());
+        if let Some(order) = self.lock().get(&key) {
+            return Ok(order.clone());
+        }
+        // Sorted without the lock; a concurrent request may sort it as well.
+        let order: Arc<[usize]> = sort_order(records, sort)?.into();
+        let mut orders = self.lock();
+        if orders.len() < MAX_SORTED_ORDERS {
+            orders.insert(key, order.clone());
+        }
+        Ok(order)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<OrderKey, Arc<[usize]>>> {
+        self.orders.lock().expect("sorted orders lock poisoned")
+    }
+}
+
+/// Sorts `records` of `data_set`, cuts out the page selected by the cursor
+/// and limit and projects each record onto the requested fields.
+///
+/// The sorted order is taken from and kept in `sorted` under the name `list`,
+/// if given. Records without a sort field sort last in either direction.
+/// Records that compare equal keep the order of the data set.
+pub(crate) fn page<T: Serialize>(
+    data_set: &DataSet,
+    list: &str,
+    records: &[T],
+    options: &ListOptions,
+    sorted: Option<&SortedOrders>,
+) -> Result<Page, QueryError> {
+    let total = records.len();
+    let offset = options.cursor.as_ref().map_or(0, |cursor| cursor.offset);
+    if offset > total {
+        return Err(QueryError::CursorPastEnd { total });
+    }
+    let end = total.min(offset + options.limit);
+
+    let mut items = if options.sort.is_empty() {
+        to_values(&records[offset..end])?
+    } else {
+        let order = match sorted {
+            Some(sorted) => sorted.get_or_sort(list, &options.sort, records)?,
+            None => sort_order(records, &options.sort)?.into(),
+        };
+        order[offset..end]
+            .iter()
+            .map(|&i| to_value(&records[i]))
+            .collect::<Result<_, _>>()?
+    };
+    if let Some(fields) = &options.fields {
+        items = items
+            .into_iter()
+            .map(|item| project(item, fields))
+            .collect();
+    }
+
+    let next_cursor = (end < total).then(|| {
+        Cursor {
+            hash: data_set.stats().hash().to_string(),
+            offset: end,
+        }
+        .to_string()
+    });
+    Ok(Page::new(items, total, next_cursor))
+}
+
+fn to_values<T: Serialize>(records: &[T]) -> Result<Vec<Value>, QueryError> {
+    records.iter().map(to_value).collect()
+}
+
+fn to_value<T: Serialize>(record: &T) -> Result<Value, QueryError> {
+    serde_json::to_value(record).map_err(|e| QueryError::Serialize(Arc::new(e)))
+}
+
+/// Positions of `records` in the order of `sort`.
+fn sort_order<T: Serialize>(records: &[T], sort: &[SortKey]) -> Result<Vec<usize>, QueryError> {
+    let values = to_values(records)?;
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| compare(&values[a], &values[b], sort));
+    Ok(order)
+}
+
+fn compare(a: &Value, b: &Value, sort: &[SortKey]) -> Ordering {
+    sort.iter()
+        .map(
+            |key| match (present(a.get(&key.field)), present(b.get(&key.field))) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) if key.descending => compare_values(a, b).reverse(),
+                (Some(a), Some(b)) => compare_values(a, b),
+            },
+        )
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn present(value: Option<&Value>) -> Option<&Value> {
+    value.filter(|value| !value.is_null())
+}
+
+/// Orders values of the same JSON type by value, and values of different
+/// types by type.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+fn project(item: Value, fields: &[String]) -> Value {
+    match item {
+        Value::Object(mut record) => Value::Object(
+            fields
+                .iter()
+                .filter_map(|field| record.remove_entry(field))
+                .collect::<Map<String, Value>>(),
+        ),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn query(parameters: &str) -> ListQuery {
+        serde_json::from_value(
+            parameters
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, value)| (name.to_string(), Value::from(value)))
+                .collect::<Map<String, Value>>()
+                .into(),
+        )
+        .unwrap()
+    }
+
+    fn options(parameters: &str) -> ListOptions {
+        ListOptions::parse(&query(parameters)).unwrap()
+    }
+
+    fn data_set() -> DataSet {
+        DataSet::from_payload(Payload::default(), Utc::now()).unwrap()
+    }
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"id": "a", "rank": 3, "name": "Alpha"}),
+            json!({"id": "b", "name": "Beta"}),
+            json!({"id": "c", "rank": 1, "name": "Gamma"}),
+            json!({"id": "d", "rank": 3, "name": "Delta"}),
+        ]
+    }
+
+    fn ids(page: &Page) -> Vec<String> {
+        serde_json::to_value(page).unwrap()["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn next_cursor(page: &Page) -> Option<String> {
+        serde_json::to_value(page).unwrap()["next_cursor"]
+            .as_str()
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn pages_follow_the_cursor() {
+        let data_set = data_set();
+        let first = page(&data_set, "records", &records(), &options("limit=3"), None).unwrap();
+        let cursor = next_cursor(&first).unwrap();
+        let second = page(
+            &data_set,
+            "records",
+            &records(),
+            &options(&format!("limit=3&cursor={}", cursor)),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(ids(&first), vec!["a", "b", "c"]);
+        assert_eq!(cursor, format!("{}.3", data_set.stats().hash()));
+        assert_eq!(ids(&second), vec!["d"]);
+        assert_eq!(next_cursor(&second), None);
+    }
+
+    #[test]
+    fn sorts_by_several_fields_with_missing_values_last() {
+        let data_set = data_set();
+        let ascending = page(
+            &data_set,
+            "records",
+            &records(),
+            &options("sort=rank,name"),
+            None,
+        )
+        .unwrap();
+        let descending = page(
+            &data_set,
+            "records",
+            &records(),
+            &options("sort=-rank,name"),
+            None,
+        )
+        .unwrap();
+        let second_page = page(
+            &data_set,
+            "records",
+            &records(),
+            &options(&format!(
+                "sort=-rank,name&limit=2&cursor={}.2",
+                data_set.stats().hash()
+            )),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(ids(&ascending), vec!["c", "a", "d", "b"]);
+        assert_eq!(ids(&descending), vec!["a", "d", "c", "b"]);
+        assert_eq!(ids(&second_page), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn sorted_order_is_kept_for_the_next_pages() {
+        let data_set = data_set();
+        let sorted = SortedOrders::default();
+        let first = page(
+            &data_set,
+            "records",
+            &records(),
+            &options("sort=-rank,name&limit=2"),
+            Some(&sorted),
+        )
+        .unwrap();
+        // The kept order is sliced, the records are not sorted again.
+        let mut reversed = records();
+        reversed.reverse();
+        let second = page(
+            &data_set,
+            "records",
+            &reversed,
+            &options(&format!(
+                "sort=-rank,name&limit=2&cursor={}",
+                next_cursor(&first).unwrap()
+            )),
+            Some(&sorted),
+        )
+        .unwrap();
+
+        assert_eq!(ids(&first), vec!["a", "d"]);
+        assert_eq!(ids(&second), vec!["b", "c"]);
+        assert_eq!(sorted.lock().len(), 1);
+    }
+
+    #[test]
+    fn projects_onto_the_requested_fields() {
+        let page = page(
+            &data_set(),
+            "records",
+            &records(),
+            &options("fields=id,rank&limit=2"),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&page).unwrap()["items"],
+            json!([{"id": "a", "rank": 3}, {"id": "b"}])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        for parameters in [
+            "limit=0",
+            "limit=1001",
+            "limit=ten",
+            "cursor=abc",
+            "cursor=0011.x",
+            "sort=",
+            "sort=name,,id",
+            "sort=-",
+            "fields=name;drop",
+        ] {
+            assert!(
+                ListOptions::parse(&query(parameters)).is_err(),
+                "{}",
+                parameters
+            );
+        }
+        let past_end = options(&format!("cursor={}.5", data_set().stats().hash()));
+        assert!(page(&data_set(), "records", &records(), &past_end, None).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::access_log::{self, AccessLogLayer};
+use crate::config::{AccessLogFormat, LogConfig, LogFormat};
+use crate::errors::ConfigError;
+use std::sync::Arc;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry, reload};
+
+/// Runtime handle to the log filter of the global subscriber.
+#[derive(Clone)]
+pub struct LogHandle {
+    filter: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogHandle {
+    /// Returns the active filter directives.
+    pub(crate) fn filter(&self) -> String {
+        self.filter
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the active filter, e.g. with `debug` or `info,rest_tokio=trace`.
+    pub(crate) fn set_filter(&self, directives: &str) -> Result<(), ConfigError> {
+        let filter = parse_filter(directives)?;
+        self.filter
+            .reload(filter)
+            .map_err(|e| ConfigError::LogReload(Arc::new(e)))
+    }
+}
+
+#[cfg(test)]
+impl LogHandle {
+    /// Handle to a filter that is not installed, for tests that must not
+    /// replace the global subscriber.
+    pub(crate) fn detached(directives: &str) -> Self {
+        let (_, filter) = reload::Layer::<EnvFilter, Registry>::new(
+            parse_filter(directives).expect("valid filter"),
+        );
+        Self { filter }
+    }
+}
+
+/// Parses `EnvFilter` directives such as `info` or `warn,warp=debug`.
+pub(crate) fn parse_filter(directives: &str) -> Result<EnvFilter, ConfigError> {
+    EnvFilter::try_new(directives.trim()).map_err(|e| ConfigError::LogFilter {
+        directives: directives.to_string(),
+        source: Arc::new(e),
+    })
+}
+
+/// Installs the global tracing subscriber.
+///
+/// The log filter applies to the log output only. The access log is written
+/// by its own layer, so that it is neither filtered nor mixed into the
+/// formatted log events.
+pub(crate) fn init(config: &LogConfig) -> Result<LogHandle, ConfigError> {
+    let (filter, handle) = reload::Layer::new(parse_filter(&config.level)?);
+
+    let output = match config.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+    let access = (config.access != AccessLogFormat::Off).then(|| {
+        AccessLogLayer::new(config.access, std::io::stdout)
+            .with_filter(filter_fn(access_log::is_access_log))
+    });
+
+    tracing_subscriber::registry()
+        .with(
+            output
+                .with_filter(filter_fn(|metadata| {
+                    metadata.target() != access_log::TARGET
+                }))
+                .with_filter(filter),
+        )
+        .with(access)
+        .try_init()
+        .map_err(|e| ConfigError::LogSubscriber(Arc::new(e)))?;
+
+    Ok(LogHandle { filter: handle })
+}
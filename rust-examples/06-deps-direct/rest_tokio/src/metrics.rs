@@ -0,0 +1,343 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::routes;
+use chrono::Utc;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Prometheus metrics of the service. The data metrics are labeled with the
+/// name of the data set.
+pub(crate) struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    reload_attempts: IntCounterVec,
+    reload_successes: IntCounterVec,
+    reload_failures: IntCounterVec,
+    rejections: IntCounterVec,
+    last_swap_timestamp: GaugeVec,
+    seconds_since_last_swap: GaugeVec,
+    data_set_info: IntGaugeVec,
+    // Last swap per data set; read on every scrape.
+    last_swap: Mutex<HashMap<String, Swap>>,
+}
+
+struct Swap {
+    /// Unix timestamp in seconds.
+    at: f64,
+    hash: String,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some(String::from("rest")), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "HTTP requests by route, method and status.",
+            ),
+            &["route", "method", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and method.",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ]),
+            &["route", "method"],
+        )?;
+        let reload_attempts = IntCounterVec::new(
+            Opts::new(
+                "data_reload_attempts_total",
+                "Data reloads started, scheduled or on demand.",
+            ),
+            &["dataset"],
+        )?;
+        let reload_successes = IntCounterVec::new(
+            Opts::new(
+                "data_reload_successes_total",
+                "Data reloads that downloaded and checked the data, changed or not.",
+            ),
+            &["dataset"],
+        )?;
+        let reload_failures = IntCounterVec::new(
+            Opts::new(
+                "data_reload_failures_total",
+                "Data reloads that failed to download or decode the data.",
+            ),
+            &["dataset"],
+        )?;
+        let rejections = IntCounterVec::new(
+            Opts::new(
+                "data_rejections_total",
+                "Downloaded data sets turned down by the validation gate.",
+            ),
+            &["dataset"],
+        )?;
+        let last_swap_timestamp = GaugeVec::new(
+            Opts::new(
+                "data_last_swap_timestamp_seconds",
+                "Unix time of the last successful swap of the data set.",
+            ),
+            &["dataset"],
+        )?;
+        let seconds_since_last_swap = GaugeVec::new(
+            Opts::new(
+                "data_seconds_since_last_swap",
+                "Seconds elapsed since the last successful swap of the data set.",
+            ),
+            &["dataset"],
+        )?;
+        let data_set_info = IntGaugeVec::new(
+            Opts::new("data_set_info", "Hash of the data set currently served."),
+            &["dataset", "hash"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(reload_attempts.clone()))?;
+        registry.register(Box::new(reload_successes.clone()))?;
+        registry.register(Box::new(reload_failures.clone()))?;
+        registry.register(Box::new(rejections.clone()))?;
+        registry.register(Box::new(last_swap_timestamp.clone()))?;
+        registry.register(Box::new(seconds_since_last_swap.clone()))?;
+        registry.register(Box::new(data_set_info.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            request_duration,
+            reload_attempts,
+            reload_successes,
+            reload_failures,
+            rejections,
+            last_swap_timestamp,
+            seconds_since_last_swap,
+            data_set_info,
+            last_swap: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) fn observe_request(&self, path: &str, method: &str, status: u16, elapsed: Duration) {
+        let route = route_label(path);
+        self.requests
+            .with_label_values(&[route, method, &status.to_string()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[route, method])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// The data metrics of the data set `name`.
+    pub(crate) fn data_set(self: &Arc<Self>, name: &str) -> DataSetMetrics {
+        // Export the counters from the start, not only once they are non-zero.
+        for counter in [
+            &self.reload_attempts,
+            &self.reload_successes,
+            &self.reload_failures,
+            &self.rejections,
+        ] {
+            counter.with_label_values(&[name]);
+        }
+        DataSetMetrics {
+            metrics: self.clone(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Drops the series of a data set that is no longer served.
+    pub(crate) fn remove_data_set(&self, name: &str) {
+        let swap = self
+            .last_swap
+            .lock()
+            .expect("metrics lock poisoned")
+            .remove(name);
+        if let Some(swap) = swap {
+            let _ = self.data_set_info.remove_label_values(&[name, &swap.hash]);
+        }
+        for counter in [
+            &self.reload_attempts,
+            &self.reload_successes,
+            &self.reload_failures,
+            &self.rejections,
+        ] {
+            let _ = counter.remove_label_values(&[name]);
+        }
+        let _ = self.last_swap_timestamp.remove_label_values(&[name]);
+        let _ = self.seconds_since_last_swap.remove_label_values(&[name]);
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub(crate) fn render(&self) -> Result<String, prometheus::Error> {
+        let now = Utc::now().timestamp_millis() as f64 / 1000.0;
+        for (name, swap) in self.last_swap.lock().expect("metrics lock poisoned").iter() {
+            self.seconds_since_last_swap
+                .with_label_values(&[name])
+                .set((now - swap.at).max(0.0));
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+/// Handle to the metrics of one data set.
+#[derive(Clone)]
+pub(crate) struct DataSetMetrics {
+    metrics: Arc<Metrics>,
+    name: String,
+}
+
+impl DataSetMetrics {
+    pub(crate) fn reload_started(&self) {
+        self.metrics
+            .reload_attempts
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+
+    pub(crate) fn reload_succeeded(&self) {
+        self.metrics
+            .reload_successes
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+
+    pub(crate) fn reload_failed(&self) {
+        self.metrics
+            .reload_failures
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+
+    pub(crate) fn data_set_rejected(&self) {
+        self.metrics
+            .rejections
+            .with_label_values(&[&self.name])
+            .inc();
+    }
+
+    /// Records that the data set with `hash` is now served.
+    pub(crate) fn data_set_swapped(&self, hash: &str) {
+        let now = Utc::now().timestamp_millis() as f64 / 1000.0;
+        let swap = Swap {
+            at: now,
+            hash: hash.to_string(),
+        };
+        let previous = self
+            .metrics
+            .last_swap
+            .lock()
+            .expect("metrics lock poisoned")
+            .insert(self.name.clone(), swap);
+        self.metrics
+            .last_swap_timestamp
+            .with_label_values(&[&self.name])
+            .set(now);
+
+        if let Some(previous) = previous {
+            let _ = self
+                .metrics
+                .data_set_info
+                .remove_label_values(&[&self.name, &previous.hash]);
+        }
+        self.metrics
+            .data_set_info
+            .with_label_values(&[&self.name, hash])
+            .set(1);
+    }
+}
+
+fn route_label(path: &str) -> &'static str {
+    routes::find(path).map_or("unmatched", |route| route.path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_reports_requests_reloads_and_hash() {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        metrics.observe_request("/stats", "GET", 200, Duration::from_millis(3));
+        metrics.observe_request("/does/not/exist", "GET", 404, Duration::from_millis(1));
+        metrics.observe_request(
+            "/admin/rollback/d71370c75f19dcc2",
+            "POST",
+            200,
+            Duration::ZERO,
+        );
+        let data_set = metrics.data_set("default");
+        data_set.reload_started();
+        data_set.reload_failed();
+        data_set.data_set_swapped("d71370c75f19dcc2");
+        data_set.data_set_swapped("00000000000000ff");
+
+        let text = metrics.render().unwrap();
+
+        assert!(
+            text.contains(
+                r#"rest_http_requests_total{method="GET",route="/stats",status="200"} 1"#
+            )
+        );
+        assert!(text.contains(
+            r#"rest_http_requests_total{method="GET",route="unmatched",status="404"} 1"#
+        ));
+        assert!(text.contains(
+            r#"rest_http_requests_total{method="POST",route="/admin/rollback/{hash}",status="200"} 1"#
+        ));
+        assert!(text.contains(
+            r#"rest_http_request_duration_seconds_count{method="GET",route="/stats"} 1"#
+        ));
+        assert!(text.contains(r#"rest_data_reload_attempts_total{dataset="default"} 1"#));
+        assert!(text.contains(r#"rest_data_reload_failures_total{dataset="default"} 1"#));
+        assert!(text.contains(r#"rest_data_reload_successes_total{dataset="default"} 0"#));
+        assert!(text.contains(r#"rest_data_rejections_total{dataset="default"} 0"#));
+        assert!(
+            text.contains(r#"rest_data_set_info{dataset="default",hash="00000000000000ff"} 1"#)
+        );
+        assert!(!text.contains("d71370c75f19dcc2"));
+        assert!(text.contains(r#"rest_data_seconds_since_last_swap{dataset="default"}"#));
+    }
+
+    #[test]
+    fn removed_data_set_is_no_longer_reported() {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        metrics
+            .data_set("default")
+            .data_set_swapped("d71370c75f19dcc2");
+        let rates = metrics.data_set("rates");
+        rates.reload_started();
+        rates.data_set_swapped("00000000000000ff");
+
+        metrics.remove_data_set("rates");
+
+        let text = metrics.render().unwrap();
+        assert!(
+            text.contains(r#"rest_data_set_info{dataset="default",hash="d71370c75f19dcc2"} 1"#)
+        );
+        assert!(!text.contains("rates"));
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::config::{self, NotifyConfig};
+use crate::errors;
+use crate::errors::{ConfigError, ConfigProblem, NotifyError};
+use crate::types::notification::{Notification, NotificationEvent};
+use crate::types::reload_status::ReloadStatus;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{Instrument, info, info_span, warn};
+
+/// Upper bound for delivering one notification to the webhook or the command.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tells someone when reloads keep failing and when they work again.
+///
+/// A `RELOAD_FAILING` notification goes out when the number of consecutive
+/// failed reloads reaches the threshold, and a `RELOAD_RECOVERED` notification
+/// with the next successful reload after that. A `DATASET_REJECTED`
+/// notification goes out right away for every data set the validation gate
+/// turns down. Notifications are delivered one at a time by a background
+/// task, in the order they were sent, so a slow receiver never holds up a
+/// reload and never sees a recovery before the failure it ends.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Notifier {
+    webhook: Option<Webhook>,
+    command: Option<PathBuf>,
+    failure_threshold: u32,
+    /// Queue of the delivery task, started with the first notification. It is
+    /// shared by all clones and ends when the last clone is dropped.
+    queue: Arc<OnceLock<mpsc::UnboundedSender<Notification>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Webhook {
+    url: reqwest::Url,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub(crate) fn from_config(config: &NotifyConfig) -> Result<Self, ConfigError> {
+        if config.failure_threshold == 0 {
+            return Err(ConfigError::Invalid(vec![ConfigProblem::TooSmall {
+                key: String::from("notify.failure_threshold"),
+                min: 1,
+            }]));
+        }
+        let webhook = match &config.webhook_url {
+            Some(url) => Some(Webhook::new(url)?),
+            None => None,
+        };
+        Ok(Self {
+            webhook,
+            command: config.command.as_ref().map(PathBuf::from),
+            failure_threshold: config.failure_threshold,
+            queue: Arc::default(),
+        })
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.webhook.is_some() || self.command.is_some()
+    }
+
+    /// Call after a failed reload has been recorded in `status`, with the time
+    /// of the reloader's clock.
+    pub(crate) fn reload_failed(&self, source: &str, status: &ReloadStatus, now: DateTime<Utc>) {
+        if status.consecutive_failures() == self.failure_threshold {
+            self.send(Notification::new(
+                NotificationEvent::ReloadFailing,
+                source.to_string(),
+                status.consecutive_failures(),
+                status.last_error().map(str::to_string),
+                timestamp(now),
+            ));
+        }
+    }
+
+    /// Call after a successful reload with the failures that preceded it.
+    pub(crate) fn reload_succeeded(
+        &self,
+        source: &str,
+        previous_failures: u32,
+        now: DateTime<Utc>,
+    ) {
+        if previous_failures >= self.failure_threshold && self.is_enabled() {
+            self.send(Notification::new(
+                NotificationEvent::ReloadRecovered,
+                source.to_string(),
+                previous_failures,
+                None,
+                timestamp(now),
+            ));
+        }
+    }
+
+    /// Call after a rejected data set has been recorded in `status`.
+    pub(crate) fn data_set_rejected(
+        &self,
+        source: &str,
+        status: &ReloadStatus,
+        now: DateTime<Utc>,
+    ) {
+        self.send(Notification::new(
+            NotificationEvent::DatasetRejected,
+            source.to_string(),
+            status.consecutive_failures(),
+            status.last_error().map(str::to_string),
+            timestamp(now),
+        ));
+    }
+
+    fn send(&self, notification: Notification) {
+        if !self.is_enabled() {
+            return;
+        }
+        let queue = self.queue.get_or_init(|| self.start_delivery());
+        if queue.send(notification).is_err() {
+            warn!("Notification dropped, the delivery task has ended");
+        }
+    }
+
+    /// Spawns the task that delivers the queued notifications in order.
+    fn start_delivery(&self) -> mpsc::UnboundedSender<Notification> {
+        let (queue, mut notifications) = mpsc::unbounded_channel::<Notification>();
+        let webhook = self.webhook.clone();
+        let command = self.command.clone();
+        tokio::spawn(async move {
+            while let Some(notification) = notifications.recv().await {
+                let span = info_span!("notify", event = ?notification.event());
+                deliver(webhook.as_ref(), command.as_deref(), &notification)
+                    .instrument(span)
+                    .await;
+            }
+        });
+        queue
+    }
+}
+
+/// Delivers `notification` to the webhook, then to the command. Failures are
+/// logged; the notification is not retried.
+async fn deliver(webhook: Option<&Webhook>, command: Option<&Path>, notification: &Notification) {
+    if let Some(webhook) = webhook {
+        match webhook.post(notification).await {
+            Ok(()) => info!(url = %config::redact_url(webhook.url.as_str()), "Notification sent"),
+            Err(e) => warn!(error = %errors::report(&e), "Failed to send notification"),
+        }
+    }
+    if let Some(command) = command {
+        match run_command(command, notification).await {
+            Ok(()) => info!(command = %command.display(), "Notify command finished"),
+            Err(e) => warn!(error = %errors::report(&e), "Notify command failed"),
+        }
+    }
+}
+
+impl Webhook {
+    fn new(url: &str) -> Result<Self, ConfigError> {
+        let url = reqwest::Url::parse(url).map_err(|e| ConfigError::WebhookUrl {
+            url: url.to_string(),
+            source: Arc::new(e),
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ConfigError::WebhookScheme(config::redact_url(url.as_str())));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .map_err(|e| ConfigError::HttpClient(Arc::new(e)))?;
+        Ok(Self { url, client })
+    }
+
+    async fn post(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let body =
+            serde_json::to_vec(notification).map_err(|e| NotifyError::Encode(Arc::new(e)))?;
+        self.client
+            .post(self.url.clone())
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| NotifyError::Webhook {
+                url: config::redact_url(self.url.as_str()),
+                source: Arc::new(e.without_url()),
+            })?;
+        Ok(())
+    }
+}
+
+/// Runs `command` with the notification as JSON on stdin and the event name
+/// in the `NOTIFY_EVENT` environment variable.
+async fn run_command(command: &Path, notification: &Notification) -> Result<(), NotifyError> {
+    let body = serde_json::to_vec(notification).map_err(|e| NotifyError::Encode(Arc::new(e)))?;
+    let event = serde_json::to_value(notification.event())
+        .ok()
+        .and_then(|event| event.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut child = tokio::process::Command::new(command)
+        .env("NOTIFY_EVENT", event)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| NotifyError::Command {
+            command: command.to_path_buf(),
+            source: Arc::new(e),
+        })?;
+
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&body).await?;
+        }
+        child.wait().await
+    };
+    let status = tokio::time::timeout(DELIVERY_TIMEOUT, run)
+        .await
+        .map_err(|_| NotifyError::CommandTimeout {
+            command: command.to_path_buf(),
+        })?
+        .map_err(|e| NotifyError::Command {
+            command: command.to_path_buf(),
+            source: Arc::new(e),
+        })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(NotifyError::CommandFailed {
+            command: command.to_path_buf(),
+            status,
+        })
+    }
+}
+
+fn timestamp(now: DateTime<Utc>) -> String {
+    now.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::Value;
+    use std::net::SocketAddr;
+    use tokio::sync::mpsc;
+    use warp::Filter;
+
+    /// Receives notifications on an ephemeral local port.
+    fn receiver() -> (SocketAddr, mpsc::UnboundedReceiver<Value>) {
+        slow_receiver("", Duration::ZERO)
+    }
+
+    /// Receives notifications on an ephemeral local port, and takes those of
+    /// `event` only after `delay`.
+    fn slow_receiver(
+        event: &'static str,
+        delay: Duration,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let route = warp::post()
+            .and(warp::path("hook"))
+            .and(warp::body::json())
+            .then(move |body: Value| {
+                let tx = tx.clone();
+                async move {
+                    if body["event"] == event {
+                        tokio::time::sleep(delay).await;
+                    }
+                    tx.send(body).unwrap();
+                    warp::reply()
+                }
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (addr, rx)
+    }
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Value>) -> Value {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("no notification received")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn webhook_fires_at_threshold_and_on_recovery() {
+        // The recovery is sent while the receiver still holds on to the
+        // failure, and must only be delivered after it.
+        let (addr, mut rx) = slow_receiver("RELOAD_FAILING", Duration::from_millis(200));
+        let notifier = Notifier::from_config(&NotifyConfig {
+            webhook_url: Some(format!("http://{}/hook", addr)),
+            command: None,
+            failure_threshold: 2,
+        })
+        .unwrap();
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 2, 0, 0).unwrap();
+        let mut status = ReloadStatus::default();
+        for minute in 0..3 {
+            let now = start + chrono::Duration::minutes(minute);
+            status.record_failure(now, String::from("connection refused"));
+            notifier.reload_failed("builtin", &status, now);
+        }
+        let recovered_at = start + chrono::Duration::minutes(3);
+        notifier.reload_succeeded("builtin", status.consecutive_failures(), recovered_at);
+
+        let failing = next(&mut rx).await;
+        assert_eq!(failing["event"], "RELOAD_FAILING");
+        assert_eq!(failing["consecutive_failures"], 2);
+        assert_eq!(failing["error"], "connection refused");
+        assert_eq!(failing["timestamp"], "2024-01-01T02:01:00Z");
+        // The third failure does not notify again.
+        let recovered = next(&mut rx).await;
+        assert_eq!(recovered["event"], "RELOAD_RECOVERED");
+        assert_eq!(recovered["consecutive_failures"], 3);
+        assert!(recovered.get("error").is_none());
+        assert_eq!(recovered["timestamp"], "2024-01-01T02:03:00Z");
+    }
+
+    #[tokio::test]
+    async fn recovery_below_threshold_is_silent() {
+        let (addr, mut rx) = receiver();
+        let notifier = Notifier::from_config(&NotifyConfig {
+            webhook_url: Some(format!("http://{}/hook", addr)),
+            command: None,
+            failure_threshold: 2,
+        })
+        .unwrap();
+
+        let mut status = ReloadStatus::default();
+        status.record_failure(Utc::now(), String::from("timeout"));
+        notifier.reload_failed("builtin", &status, Utc::now());
+        notifier.reload_succeeded("builtin", status.consecutive_failures(), Utc::now());
+
+        let silent = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await;
+        assert!(silent.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejection_notifies_below_threshold() {
+        let (addr, mut rx) = receiver();
+        let notifier = Notifier::from_config(&NotifyConfig {
+            webhook_url: Some(format!("http://{}/hook", addr)),
+            command: None,
+            failure_threshold: 3,
+        })
+        .unwrap();
+
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 2, 0, 0).unwrap();
+        let mut status = ReloadStatus::default();
+        status.record_failure(now, String::from("Invalid payload: ..."));
+        notifier.data_set_rejected("builtin", &status, now);
+
+        let rejected = next(&mut rx).await;
+        assert_eq!(rejected["event"], "DATASET_REJECTED");
+        assert_eq!(rejected["consecutive_failures"], 1);
+        assert_eq!(rejected["error"], "Invalid payload: ...");
+        assert_eq!(rejected["timestamp"], "2024-01-01T02:00:00Z");
+    }
+
+    // The command is a shell script.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_receives_notification_on_stdin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir();
+        let out = dir.join(format!("notify_{}.out", std::process::id()));
+        let script = dir.join(format!("notify_{}.sh", std::process::id()));
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$NOTIFY_EVENT\" > {0}\ncat >> {0}\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let notification = Notification::new(
+            NotificationEvent::ReloadFailing,
+            String::from("builtin"),
+            3,
+            Some(String::from("timeout")),
+            String::from("2024-01-01T02:00:00Z"),
+        );
+
+        let result = run_command(&script, &notification).await;
+        let output = std::fs::read_to_string(&out);
+        std::fs::remove_file(&script).unwrap();
+        let _ = std::fs::remove_file(&out);
+
+        result.unwrap();
+        let output = output.unwrap();
+        let (event, body) = output.split_once('\n').unwrap();
+        assert_eq!(event, "RELOAD_FAILING");
+        assert_eq!(
+            serde_json::from_str::<Notification>(body).unwrap(),
+            notification
+        );
+    }
+}
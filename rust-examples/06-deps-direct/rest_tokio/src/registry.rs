@@ -0,0 +1,468 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::cache::SnapshotCache;
+use crate::clock::SharedClock;
+use crate::config::{Config, DataSetConfig};
+use crate::errors;
+use crate::errors::ConfigError;
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
+use crate::reload::Reloader;
+use crate::schedule::{ReloadSchedule, Scheduler};
+use crate::source::DataSource;
+use crate::types::MetaDataStore;
+use crate::types::active_data_set::ActiveDataSet;
+use crate::types::data_set::DataSet;
+use crate::types::data_set_summary::DataSetSummary;
+use crate::types::reload_status::ReloadStatus;
+use crate::validate::Validator;
+use arc_swap::ArcSwap;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::{Instrument, error, info, info_span};
+
+/// Name of the data set configured in `[data]` and `[reload]`.
+pub(crate) const DEFAULT: &str = "default";
+
+/// Directory below `data.cache_dir` that holds the cache of each named data
+/// set, apart from the files of the default data set such as its quarantine.
+const CACHE_DIR: &str = "datasets";
+
+/// The data sets served by name, each with its own source, schedule, reloads
+/// and history, and swapped independently of the others.
+///
+/// The default data set lives for the whole process. The named data sets of
+/// the `[datasets]` config section are started, restarted and stopped as the
+/// config changes.
+pub(crate) struct Registry {
+    data_sets: ArcSwap<BTreeMap<String, Arc<Registered>>>,
+    metrics: Arc<Metrics>,
+    notifier: Notifier,
+    clock: SharedClock,
+    // Serializes config updates, so that no data set is started twice.
+    updating: tokio::sync::Mutex<()>,
+}
+
+/// A data set in the registry.
+pub(crate) struct Registered {
+    source: String,
+    /// `None` for the default data set, which is not managed by the config.
+    config: Option<DataSetConfig>,
+    reloader: Arc<Reloader>,
+    schedule: Arc<ReloadSchedule>,
+    scheduler: Mutex<Option<Scheduler>>,
+}
+
+impl Registered {
+    pub(crate) fn store(&self) -> &MetaDataStore {
+        self.reloader.store()
+    }
+
+    pub(crate) fn reloader(&self) -> &Arc<Reloader> {
+        &self.reloader
+    }
+
+    pub(crate) fn schedule(&self) -> &Arc<ReloadSchedule> {
+        &self.schedule
+    }
+
+    /// Ends the event streams of the data set and stops its reloads, waiting
+    /// for a running reload to finish.
+    async fn stop(&self) {
+        self.reloader.events().close();
+        let scheduler = self
+            .scheduler
+            .lock()
+            .expect("registry lock poisoned")
+            .take();
+        if let Some(scheduler) = scheduler {
+            scheduler.shutdown().await;
+        }
+    }
+}
+
+impl Registry {
+    /// Starts with the default data set, whose reloads are already scheduled.
+    /// The named data sets run on the clock of its reloader.
+    pub(crate) fn new(
+        source: String,
+        reloader: Arc<Reloader>,
+        schedule: Arc<ReloadSchedule>,
+        scheduler: Scheduler,
+        metrics: Arc<Metrics>,
+        notifier: Notifier,
+    ) -> Self {
+        let clock = reloader.clock().clone();
+        let default = Registered {
+            source,
+            config: None,
+            reloader,
+            schedule,
+            scheduler: Mutex::new(Some(scheduler)),
+        };
+        Self {
+            data_sets: ArcSwap::from_pointee(BTreeMap::from([(
+                DEFAULT.to_string(),
+                Arc::new(default),
+            )])),
+            metrics,
+            notifier,
+            clock,
+            updating: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Arc<Registered>> {
+        self.data_sets.load().get(name).cloned()
+    }
+
+    /// The default data set, which is registered for the whole process.
+    pub(crate) fn default_data_set(&self) -> Arc<Registered> {
+        self.get(DEFAULT)
+            .expect("default data set is never stopped")
+    }
+
+    /// Name, source and stats of every data set, ordered by name.
+    pub(crate) fn list(&self) -> Vec<DataSetSummary> {
+        self.data_sets
+            .load()
+            .iter()
+            .map(|(name, registered)| {
+                DataSetSummary::new(
+                    name.clone(),
+                    registered.source.clone(),
+                    registered.store().load().stats().clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Brings the named data sets in line with `config.datasets`: new ones are
+    /// started, removed ones stopped and changed ones restarted. Unchanged
+    /// data sets keep running without interruption, and restarted ones keep
+    /// serving their data until the new source has loaded.
+    pub(crate) async fn apply(&self, config: &Config) {
+        let _updating = self.updating.lock().await;
+        let mut data_sets = BTreeMap::clone(&self.data_sets.load());
+
+        let outdated: Vec<String> = data_sets
+            .iter()
+            .filter(|(name, registered)| match &registered.config {
+                Some(running) => config.datasets.get(*name) != Some(running),
+                None => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        let mut restarted = BTreeMap::new();
+        for name in outdated {
+            if let Some(registered) = data_sets.remove(&name) {
+                registered.stop().await;
+                if config.datasets.contains_key(&name) {
+                    restarted.insert(name.clone(), registered.reloader.clone());
+                } else {
+                    self.metrics.remove_data_set(&name);
+                }
+                info!(dataset = %name, "Data set stopped");
+            }
+        }
+
+        for (name, data_set) in &config.datasets {
+            if data_sets.contains_key(name) {
+                continue;
+            }
+            match self
+                .start(name, data_set, config, restarted.remove(name))
+                .await
+            {
+                Ok(registered) => {
+                    info!(dataset = %name, source = %data_set.source, "Data set started");
+                    data_sets.insert(name.clone(), Arc::new(registered));
+                }
+                Err(e) => {
+                    error!(dataset = %name, error = %errors::report(&e), "Failed to start data set")
+                }
+            }
+        }
+        self.data_sets.store(Arc::new(data_sets));
+    }
+
+    /// Ends the event streams and stops the reloads of all data sets.
+    pub(crate) async fn shutdown(&self) {
+        let data_sets = self.data_sets.load_full();
+        futures_util::future::join_all(data_sets.values().map(|registered| registered.stop()))
+            .await;
+    }
+
+    /// Starts serving the data of `previous`, the reloader of the data set
+    /// being restarted, or else the cached data set, or an empty one without a
+    /// cache, and loads the live data in the background. A restarted data set
+    /// keeps the load status and the snapshot history of `previous`.
+    async fn start(
+        &self,
+        name: &str,
+        data_set: &DataSetConfig,
+        config: &Config,
+        previous: Option<Arc<Reloader>>,
+    ) -> Result<Registered, ConfigError> {
+        let data_source =
+            DataSource::from_location(&data_set.source).map_err(|e| ConfigError::Source {
+                location: data_set.source.clone(),
+                source: e,
+            })?;
+        let schedule = Arc::new(ReloadSchedule::from_config(
+            &data_set.reload(&config.reload),
+        )?);
+        let cache = config
+            .data
+            .cache_dir
+            .as_deref()
+            .map(|dir| SnapshotCache::new(&dir.join(CACHE_DIR).join(name)));
+        let metrics = self.metrics.data_set(name);
+        let mut status = ReloadStatus::default();
+        let store = match &previous {
+            Some(previous) => {
+                status = previous.status();
+                previous.store().clone()
+            }
+            None => {
+                let cached = match &cache {
+                    Some(cache) => cache.restore().await,
+                    None => None,
+                };
+                let initial = match cached {
+                    Some(data_set) => {
+                        info!(
+                            dataset = %name,
+                            hash = %data_set.stats().hash(),
+                            downloaded = data_set.stats().download_timestamp(),
+                            "Serving cached data until the first load"
+                        );
+                        status.record_cache_fallback(
+                            data_set.stats().download_timestamp().to_string(),
+                        );
+                        metrics.data_set_swapped(data_set.stats().hash());
+                        data_set
+                    }
+                    None => DataSet::default(),
+                };
+                Arc::new(ArcSwap::from_pointee(
+                    ActiveDataSet::prepare(Arc::new(initial), self.clock.now()).await,
+                ))
+            }
+        };
+        let validator = Validator::from_config(&data_set.validation(&config.validation));
+        let mut reloader = Reloader::new(
+            store,
+            data_source,
+            metrics,
+            self.notifier.clone(),
+            status,
+            config.data.history_size,
+            cache,
+        )
+        .with_validator(validator)
+        .with_clock(self.clock.clone());
+        if let Some(previous) = &previous {
+            reloader = reloader.with_history_of(previous);
+        }
+        let reloader = Arc::new(reloader);
+
+        let span = info_span!("dataset", name);
+        let initial = reloader.clone();
+        tokio::spawn(
+            async move {
+                // Failures are logged and counted by the reloader.
+                let _ = initial.reload().await;
+            }
+            .instrument(span.clone()),
+        );
+        let scheduler = span.in_scope(|| Scheduler::start(schedule.clone(), reloader.clone()));
+
+        Ok(Registered {
+            source: data_set.source.clone(),
+            config: Some(data_set.clone()),
+            reloader,
+            schedule,
+            scheduler: Mutex::new(Some(scheduler)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReloadConfig;
+    use chrono::Utc;
+
+    async fn registry() -> Registry {
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let store: MetaDataStore = Arc::new(ArcSwap::from_pointee(ActiveDataSet::new(
+            Arc::new(DataSet::default()),
+            Utc::now(),
+        )));
+        let reloader = Arc::new(Reloader::new(
+            store,
+            DataSource::Builtin,
+            metrics.data_set(DEFAULT),
+            Notifier::default(),
+            ReloadStatus::default(),
+            1,
+            None,
+        ));
+        let schedule = Arc::new(ReloadSchedule::from_config(&ReloadConfig::default()).unwrap());
+        let scheduler = Scheduler::start(schedule.clone(), reloader.clone());
+        Registry::new(
+            String::from("builtin"),
+            reloader,
+            schedule,
+            scheduler,
+            metrics,
+            Notifier::default(),
+        )
+    }
+
+    fn config(data_sets: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        for (name, source) in data_sets {
+            config.datasets.insert(
+                name.to_string(),
+                DataSetConfig {
+                    source: source.to_string(),
+                    schedule: None,
+                    timezone: None,
+                    validation: None,
+                },
+            );
+        }
+        config
+    }
+
+    fn names(registry: &Registry) -> Vec<String> {
+        registry.data_sets.load().keys().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn apply_starts_restarts_and_stops_data_sets() {
+        let registry = registry().await;
+
+        registry
+            .apply(&config(&[("rates", "builtin"), ("venues", "builtin")]))
+            .await;
+        assert_eq!(names(&registry), vec!["default", "rates", "venues"]);
+        let rates = registry.get("rates").unwrap();
+
+        registry
+            .apply(&config(&[
+                ("rates", "builtin"),
+                ("venues", "/tmp/venues.json"),
+            ]))
+            .await;
+        assert!(Arc::ptr_eq(&rates, &registry.get("rates").unwrap()));
+        assert_eq!(registry.get("venues").unwrap().source, "/tmp/venues.json");
+
+        registry.apply(&config(&[])).await;
+        assert_eq!(names(&registry), vec!["default"]);
+        registry.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn restarted_data_set_serves_its_old_data_until_loaded() {
+        let file =
+            std::env::temp_dir().join(format!("registry_restart_{}.json", std::process::id()));
+        let source = file.to_str().unwrap();
+        std::fs::write(&file, r#"{"assets": [{"id": "BTC"}]}"#).unwrap();
+        let registry = registry().await;
+        registry.apply(&config(&[("rates", source)])).await;
+        let rates = registry.get("rates").unwrap();
+        rates.reloader.reload().await.unwrap();
+        let first = rates.store().load().stats().clone();
+        std::fs::write(&file, r#"{"assets": [{"id": "BTC"}, {"id": "ETH"}]}"#).unwrap();
+        rates.reloader.reload().await.unwrap();
+        let loaded = rates.store().load().stats().clone();
+        let loaded_at = rates.reloader.status().last_success();
+
+        registry
+            .apply(&config(&[("rates", "/nonexistent/rates.json")]))
+            .await;
+
+        let restarted = registry.get("rates").unwrap();
+        assert!(!Arc::ptr_eq(&rates, &restarted));
+        assert_eq!(restarted.store().load().stats(), &loaded);
+        // The kept data counts as loaded, and the history goes on.
+        assert!(loaded_at.is_some());
+        assert_eq!(restarted.reloader.status().last_success(), loaded_at);
+        assert_eq!(
+            restarted
+                .reloader
+                .previous(loaded.hash())
+                .map(|snapshot| snapshot.stats().clone()),
+            Some(first)
+        );
+        registry.shutdown().await;
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[tokio::test]
+    async fn started_data_set_serves_its_cache_until_loaded() {
+        let dir = std::env::temp_dir().join(format!("registry_cache_{}", std::process::id()));
+        let cached = DataSet::from_payload(
+            serde_json::from_str(r#"{"assets": [{"id": "BTC"}]}"#).unwrap(),
+            Utc::now(),
+        )
+        .unwrap();
+        SnapshotCache::new(&dir.join(CACHE_DIR).join("rates"))
+            .store(&cached)
+            .await
+            .unwrap();
+        let mut config = config(&[("rates", "/nonexistent/rates.json")]);
+        config.data.cache_dir = Some(dir.clone());
+        let registry = registry().await;
+
+        registry.apply(&config).await;
+
+        let rates = registry.get("rates").unwrap();
+        assert_eq!(rates.store().load().stats(), cached.stats());
+        assert!(rates.reloader.status().cached_download().is_some());
+        registry.shutdown().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn named_data_set_caches_apart_from_the_default_data_set() {
+        let dir = std::env::temp_dir().join(format!("registry_apart_{}", std::process::id()));
+        let mut config = config(&[("quarantine", "builtin")]);
+        config.data.cache_dir = Some(dir.clone());
+        let registry = registry().await;
+
+        registry.apply(&config).await;
+        registry
+            .get("quarantine")
+            .unwrap()
+            .reloader
+            .reload()
+            .await
+            .unwrap();
+
+        let restored = SnapshotCache::new(&dir.join(CACHE_DIR).join("quarantine"))
+            .restore()
+            .await;
+        let quarantine_dir = dir.join("quarantine").exists();
+        registry.shutdown().await;
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(restored.is_some());
+        assert!(!quarantine_dir);
+    }
+}
@@ -0,0 +1,639 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::cache::SnapshotCache;
+use crate::clock::{Clock, SharedClock, SystemClock};
+use crate::diff;
+use crate::errors;
+use crate::errors::LoadError;
+use crate::events::Events;
+use crate::metrics::DataSetMetrics;
+use crate::notify::Notifier;
+use crate::source::DataSource;
+use crate::types::MetaDataStore;
+use crate::types::active_data_set::ActiveDataSet;
+use crate::types::data_set::DataSet;
+use crate::types::quarantine::{Quarantine, Rejection};
+use crate::types::reload_outcome::ReloadOutcome;
+use crate::types::reload_status::ReloadStatus;
+use crate::types::snapshot_history::SnapshotHistory;
+use crate::types::snapshot_list::SnapshotList;
+use crate::validate::{Validator, Violation};
+use chrono::SecondsFormat;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+/// Downloads the data and builds a new data set from it.
+pub(crate) async fn run_init(
+    data_source: &DataSource,
+    clock: &dyn Clock,
+) -> Result<DataSet, LoadError> {
+    let payload = data_source.fetch().await?;
+    DataSet::from_payload(payload, clock.now())
+}
+
+/// Runs the fetch, hash compare and swap cycle against the store.
+///
+/// Scheduled and on-demand reloads share one `Reloader`, so at most one reload
+/// runs at a time. A caller that arrives while a reload is in flight waits for
+/// it and receives its result instead of starting another download.
+///
+/// A changed data set is swapped in only if it passes the validator. A
+/// rejected one counts as a failed reload and is kept in a bounded quarantine,
+/// and on disk if a cache is configured, so that it can be inspected.
+///
+/// Every data set swapped into the store is kept in a bounded history, so an
+/// operator can roll back to an earlier one and pin it against further reloads.
+/// Every swap is announced to the event subscribers.
+pub(crate) struct Reloader {
+    store: MetaDataStore,
+    data_source: DataSource,
+    metrics: DataSetMetrics,
+    notifier: Notifier,
+    running: tokio::sync::Mutex<()>,
+    // Number of finished reloads and the result of the latest one.
+    completed: AtomicU64,
+    last_result: Mutex<Option<Result<ReloadOutcome, LoadError>>>,
+    status: watch::Sender<ReloadStatus>,
+    history: Mutex<SnapshotHistory>,
+    cache: Option<SnapshotCache>,
+    events: Arc<Events>,
+    validator: Validator,
+    quarantine: Mutex<Quarantine>,
+    clock: SharedClock,
+}
+
+impl Reloader {
+    pub(crate) fn new(
+        store: MetaDataStore,
+        data_source: DataSource,
+        metrics: DataSetMetrics,
+        notifier: Notifier,
+        status: ReloadStatus,
+        history_size: usize,
+        cache: Option<SnapshotCache>,
+    ) -> Self {
+        let mut history = SnapshotHistory::new(history_size);
+        if status.last_success().is_some() || status.cached_download().is_some() {
+            history.push(store.load().data_set().clone());
+        }
+        let events = Arc::new(Events::new(store.load().stats().clone()));
+        Self {
+            store,
+            data_source,
+            metrics,
+            notifier,
+            running: tokio::sync::Mutex::new(()),
+            completed: AtomicU64::new(0),
+            last_result: Mutex::new(None),
+            status: watch::Sender::new(status),
+            history: Mutex::new(history),
+            cache,
+            events,
+            validator: Validator::default(),
+            quarantine: Mutex::new(Quarantine::new(0)),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Checks changed data sets with `validator` before they are swapped in.
+    pub(crate) fn with_validator(mut self, validator: Validator) -> Self {
+        self.quarantine = Mutex::new(Quarantine::new(validator.quarantine_size()));
+        self.validator = validator;
+        self
+    }
+
+    /// Continues the snapshot history of `previous`, the reloader of the same
+    /// data set before a restart. A pinned snapshot is not pinned any more, as
+    /// the restarted data set is to load from its new source.
+    pub(crate) fn with_history_of(self, previous: &Reloader) -> Self {
+        self.history
+            .lock()
+            .expect("reload lock poisoned")
+            .extend_from(&previous.history.lock().expect("reload lock poisoned"));
+        self
+    }
+
+    /// Takes the time of loads and swaps from `clock`, which also drives the
+    /// schedule of this reloader.
+    pub(crate) fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The clock of the load status and the reload schedule.
+    pub(crate) fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Change events of the data sets swapped in by this reloader.
+    pub(crate) fn events(&self) -> &Arc<Events> {
+        &self.events
+    }
+
+    /// The store this reloader swaps data sets into.
+    pub(crate) fn store(&self) -> &MetaDataStore {
+        &self.store
+    }
+
+    /// Outcome of the loads so far, including the one at startup.
+    pub(crate) fn status(&self) -> ReloadStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Receives the status whenever a load has been recorded.
+    #[cfg(test)]
+    pub(crate) fn watch_status(&self) -> watch::Receiver<ReloadStatus> {
+        self.status.subscribe()
+    }
+
+    /// Snapshots available for rollback.
+    pub(crate) fn snapshots(&self) -> SnapshotList {
+        let active = self.store.load();
+        self.history
+            .lock()
+            .expect("reload lock poisoned")
+            .list(active.stats().hash())
+    }
+
+    /// The snapshot with `hash` from the history.
+    pub(crate) fn snapshot(&self, hash: &str) -> Option<Arc<DataSet>> {
+        self.history
+            .lock()
+            .expect("reload lock poisoned")
+            .find(hash)
+    }
+
+    /// The snapshot swapped in before the one with `hash`.
+    pub(crate) fn previous(&self, hash: &str) -> Option<Arc<DataSet>> {
+        self.history
+            .lock()
+            .expect("reload lock poisoned")
+            .previous(hash)
+    }
+
+    /// Data sets turned down by the validator.
+    pub(crate) fn quarantined(&self) -> Quarantine {
+        self.quarantine
+            .lock()
+            .expect("reload lock poisoned")
+            .clone()
+    }
+
+    /// Hash of the pinned snapshot, if reloads are suspended.
+    pub(crate) fn pinned(&self) -> Option<String> {
+        self.history
+            .lock()
+            .expect("reload lock poisoned")
+            .pinned()
+            .map(str::to_string)
+    }
+
+    /// Swaps the snapshot with `hash` back into the store and optionally pins it.
+    /// Returns `None` if no snapshot with that hash is kept.
+    pub(crate) async fn rollback(&self, hash: &str, pin: bool) -> Option<ReloadOutcome> {
+        // Wait for a running reload, so that it cannot overwrite the rollback.
+        let _running = self.running.lock().await;
+        let snapshot = {
+            let mut history = self.history.lock().expect("reload lock poisoned");
+            let snapshot = history.find(hash)?;
+            if pin {
+                history.pin(hash);
+            }
+            snapshot
+        };
+
+        let old_hash = self.store.load().stats().hash().to_string();
+        let changed = old_hash != hash;
+        if changed {
+            self.swap(snapshot.clone()).await;
+            self.metrics.data_set_swapped(hash);
+            self.persist(&snapshot).await;
+        }
+        warn!(%old_hash, new_hash = hash, pin, "Rolled back to snapshot");
+        Some(ReloadOutcome::new(changed, old_hash, hash.to_string()))
+    }
+
+    /// Resumes reloads. Returns the hash of the snapshot that was pinned.
+    pub(crate) fn unpin(&self) -> Option<String> {
+        let unpinned = self.history.lock().expect("reload lock poisoned").unpin();
+        if let Some(hash) = &unpinned {
+            info!(%hash, "Unpinned snapshot; reloads resume");
+        }
+        unpinned
+    }
+
+    /// Re-downloads the data and swaps it into the store if its hash changed.
+    ///
+    /// While a snapshot is pinned, nothing is downloaded and the store is left as it is.
+    pub(crate) async fn reload(&self) -> Result<ReloadOutcome, LoadError> {
+        let seen = self.completed.load(Ordering::Acquire);
+        let _running = self.running.lock().await;
+
+        // A reload finished while this caller was waiting; share its result.
+        let joined = if self.completed.load(Ordering::Acquire) != seen {
+            self.last_result
+                .lock()
+                .expect("reload lock poisoned")
+                .clone()
+        } else {
+            None
+        };
+        if let Some(result) = joined {
+            debug!("Joined a reload that finished while waiting");
+            return result.map(ReloadOutcome::into_coalesced);
+        }
+
+        if let Some(hash) = self.pinned() {
+            info!(%hash, "Snapshot pinned; reload skipped");
+            return Ok(ReloadOutcome::new(false, hash.clone(), hash));
+        }
+
+        let result = self.run().await;
+        *self.last_result.lock().expect("reload lock poisoned") = Some(result.clone());
+        self.completed.fetch_add(1, Ordering::AcqRel);
+        result
+    }
+
+    async fn run(&self) -> Result<ReloadOutcome, LoadError> {
+        info!(source = %self.data_source, "Start update");
+        self.metrics.reload_started();
+
+        debug!("Re-download data");
+        let meta_data = match run_init(&self.data_source, self.clock.as_ref()).await {
+            Ok(res) => res,
+            Err(e) => return Err(self.fail(e)),
+        };
+
+        // 1) Use hash from existing metadata to determine if anything has changed
+        debug!("Load meta-data hash");
+        let guard = self.store.deref().load();
+        let old_hash = guard.stats().hash().to_string();
+        let new_hash = meta_data.stats().hash().to_string();
+
+        // 2) If no change, drop the downloaded metadata & do nothing
+        debug!("Check meta-data hash");
+        let changed = meta_data.hash() != guard.hash();
+        if !changed {
+            drop(guard);
+            drop(meta_data);
+            info!(hash = %old_hash, "Hash unchanged; no update needed");
+        } else {
+            // 3) if change, check the new metadata before it replaces the old
+            let violations = self.validator.validate(&guard, &meta_data);
+            if !violations.is_empty() {
+                drop(guard);
+                let e = self.fail(LoadError::Rejected {
+                    hash: new_hash.clone(),
+                    violations: violations.clone(),
+                });
+                self.quarantine(meta_data, violations).await;
+                return Err(e);
+            }
+
+            // 4) if valid, update the store with the new metadata
+            let changes = diff::diff(&guard, &meta_data);
+            drop(guard);
+            info!(old_hash = %old_hash, new_hash = %new_hash, %changes, "Hash changed run update");
+            self.metrics.data_set_swapped(&new_hash);
+            let meta_data = Arc::new(meta_data);
+            self.swap(meta_data.clone()).await;
+            self.history
+                .lock()
+                .expect("reload lock poisoned")
+                .push(meta_data.clone());
+            self.persist(&meta_data).await;
+        }
+        self.metrics.reload_succeeded();
+        let mut previous_failures = 0;
+        self.status.send_modify(|status| {
+            previous_failures = status.consecutive_failures();
+            status.record_success(self.clock.now());
+        });
+        self.notifier.reload_succeeded(
+            &self.data_source.to_string(),
+            previous_failures,
+            self.clock.now(),
+        );
+        debug!("Update complete");
+
+        Ok(ReloadOutcome::new(changed, old_hash, new_hash))
+    }
+
+    /// Records a failed reload and passes the error on.
+    fn fail(&self, e: LoadError) -> LoadError {
+        error!(error = %errors::report(&e), "Update failed");
+        self.metrics.reload_failed();
+        self.status
+            .send_modify(|status| status.record_failure(self.clock.now(), errors::report(&e)));
+        self.notifier.reload_failed(
+            &self.data_source.to_string(),
+            &self.status.borrow(),
+            self.clock.now(),
+        );
+        e
+    }
+
+    /// Keeps a data set the validator turned down for inspection and reports
+    /// it. Call after the rejection has been recorded as a failure.
+    pub(crate) async fn quarantine(&self, data_set: DataSet, violations: Vec<Violation>) {
+        self.metrics.data_set_rejected();
+        let rejection = Rejection::new(
+            data_set.stats().clone(),
+            self.clock.now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            violations.iter().map(ToString::to_string).collect(),
+        );
+        let new = self
+            .quarantine
+            .lock()
+            .expect("reload lock poisoned")
+            .push(rejection.clone());
+        if let Some(cache) = &self.cache {
+            match cache.quarantine(&data_set, &rejection).await {
+                Ok(path) => warn!(path = %path.display(), "Rejected data set quarantined"),
+                Err(e) => warn!(error = %errors::report(&e), "Failed to quarantine data set"),
+            }
+        }
+        // Retries download the same data again; report it once.
+        if new {
+            self.notifier.data_set_rejected(
+                &self.data_source.to_string(),
+                &self.status.borrow(),
+                self.clock.now(),
+            );
+        }
+    }
+
+    /// Makes `data_set` the active one and announces the change.
+    async fn swap(&self, data_set: Arc<DataSet>) {
+        let stats = data_set.stats().clone();
+        let active = ActiveDataSet::prepare(data_set, self.clock.now()).await;
+        let old = self.store.swap(Arc::new(active));
+        self.events.publish(old.stats().hash(), stats);
+    }
+
+    /// Writes the data set to the disk cache, if one is configured. A failed
+    /// write is logged but does not fail the reload.
+    async fn persist(&self, data_set: &DataSet) {
+        if let Some(cache) = &self.cache {
+            match cache.store(data_set).await {
+                Ok(()) => debug!(path = %cache.path().display(), "Data set cached"),
+                Err(e) => warn!(error = %errors::report(&e), "Failed to cache data set"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::source::FileSource;
+    use arc_swap::ArcSwap;
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn empty_store() -> MetaDataStore {
+        Arc::new(ArcSwap::from_pointee(ActiveDataSet::new(
+            Arc::new(DataSet::default()),
+            Utc::now(),
+        )))
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("reload_{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_only_changed_data() {
+        let path = temp_file("swap", r#"{"assets": [{"id": "BTC"}]}"#);
+        let store = empty_store();
+        let reloader = Reloader::new(
+            store.clone(),
+            DataSource::File(FileSource::new(&path)),
+            Arc::new(Metrics::new().unwrap()).data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            None,
+        );
+
+        let first = reloader.reload().await.unwrap();
+        let second = reloader.reload().await.unwrap();
+        std::fs::write(&path, r#"{"assets": [{"id": "ETH"}]}"#).unwrap();
+        let third = reloader.reload().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(first.changed());
+        assert!(!second.changed());
+        assert_eq!(second.old_hash(), first.new_hash());
+        assert!(third.changed());
+        assert_eq!(third.old_hash(), first.new_hash());
+        assert_eq!(store.load().stats().hash(), third.new_hash());
+        assert!(reloader.status().last_success().is_some());
+        assert!(reloader.status().last_failure().is_none());
+    }
+
+    #[tokio::test]
+    async fn concurrent_reloads_are_coalesced() {
+        let path = temp_file("coalesce", r#"{"assets": [{"id": "BTC"}]}"#);
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let reloader = Reloader::new(
+            empty_store(),
+            DataSource::File(FileSource::new(&path)),
+            metrics.data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            None,
+        );
+
+        // Hold the lock so both callers queue up behind a reload in flight.
+        let running = reloader.running.lock().await;
+        let first = reloader.reload();
+        let second = reloader.reload();
+        let release = async {
+            tokio::task::yield_now().await;
+            drop(running);
+        };
+        let (first, second, ()) = tokio::join!(first, second, release);
+        std::fs::remove_file(&path).unwrap();
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(first.changed());
+        assert!(second.coalesced());
+        assert_eq!(first.new_hash(), second.new_hash());
+        assert!(
+            metrics
+                .render()
+                .unwrap()
+                .contains(r#"rest_data_reload_attempts_total{dataset="default"} 1"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn failures_are_counted_until_a_reload_succeeds() {
+        let path = std::env::temp_dir().join(format!("reload_missing_{}.json", std::process::id()));
+        let reloader = Reloader::new(
+            empty_store(),
+            DataSource::File(FileSource::new(&path)),
+            Arc::new(Metrics::new().unwrap()).data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            None,
+        );
+
+        assert!(reloader.reload().await.is_err());
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(reloader.status().consecutive_failures(), 2);
+
+        std::fs::write(&path, r#"{"assets": []}"#).unwrap();
+        let result = reloader.reload().await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(reloader.status().consecutive_failures(), 0);
+        assert!(reloader.status().last_error().is_some());
+    }
+
+    #[tokio::test]
+    async fn rollback_restores_and_pins_a_snapshot() {
+        let path = temp_file("rollback", r#"{"assets": [{"id": "BTC"}]}"#);
+        let store = empty_store();
+        let reloader = Reloader::new(
+            store.clone(),
+            DataSource::File(FileSource::new(&path)),
+            Arc::new(Metrics::new().unwrap()).data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            None,
+        );
+
+        let good = reloader.reload().await.unwrap();
+        std::fs::write(&path, r#"{"assets": []}"#).unwrap();
+        let bad = reloader.reload().await.unwrap();
+        assert!(reloader.rollback("0123456789abcdef", false).await.is_none());
+
+        let rollback = reloader.rollback(good.new_hash(), true).await.unwrap();
+        assert!(rollback.changed());
+        assert_eq!(rollback.old_hash(), bad.new_hash());
+        assert_eq!(store.load().stats().hash(), good.new_hash());
+        assert_eq!(reloader.pinned().as_deref(), Some(good.new_hash()));
+
+        // Pinned: the bad data is not swapped back in.
+        let skipped = reloader.reload().await.unwrap();
+        assert!(!skipped.changed());
+        assert_eq!(store.load().stats().hash(), good.new_hash());
+
+        assert_eq!(reloader.unpin().as_deref(), Some(good.new_hash()));
+        let resumed = reloader.reload().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(resumed.changed());
+        assert_eq!(store.load().stats().hash(), bad.new_hash());
+    }
+
+    #[tokio::test]
+    async fn rejected_data_is_quarantined_and_not_swapped() {
+        use crate::config::ValidationConfig;
+
+        let truncated = r#"{"assets": [{"id": "BTC"}]}"#;
+        let path = temp_file(
+            "rejected",
+            r#"{"assets": [{"id": "BTC"}, {"id": "ETH"}, {"id": "USD"}]}"#,
+        );
+        let dir = std::env::temp_dir().join(format!("reload_quarantine_{}", std::process::id()));
+        let store = empty_store();
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let reloader = Reloader::new(
+            store.clone(),
+            DataSource::File(FileSource::new(&path)),
+            metrics.data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            Some(SnapshotCache::new(&dir)),
+        )
+        .with_validator(Validator::from_config(&ValidationConfig::default()));
+
+        let good = reloader.reload().await.unwrap();
+        std::fs::write(&path, truncated).unwrap();
+        let first = reloader.reload().await.unwrap_err();
+        let second = reloader.reload().await.unwrap_err();
+        let quarantined: Vec<_> = std::fs::read_dir(dir.join("quarantine"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let rejected = DataSet::from_payload(serde_json::from_str(truncated).unwrap(), Utc::now())
+            .unwrap()
+            .stats()
+            .hash()
+            .to_string();
+        assert_eq!(
+            first.to_string(),
+            format!(
+                "Invalid payload: data set {} rejected: assets: dropped from 3 to 1 records (66%), allowed are 50%",
+                rejected
+            )
+        );
+        assert_eq!(second.to_string(), first.to_string());
+        assert_eq!(store.load().stats().hash(), good.new_hash());
+        assert_eq!(reloader.status().consecutive_failures(), 2);
+        // The same data set is quarantined once.
+        let quarantine = serde_json::to_value(reloader.quarantined()).unwrap();
+        assert_eq!(quarantine["rejected"].as_array().unwrap().len(), 1);
+        assert_eq!(quarantined, vec![format!("{}.json", rejected)]);
+        assert!(
+            metrics
+                .render()
+                .unwrap()
+                .contains(r#"rest_data_rejections_total{dataset="default"} 2"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn swapped_data_is_cached() {
+        let path = temp_file("cached", r#"{"assets": [{"id": "BTC"}]}"#);
+        let dir = std::env::temp_dir().join(format!("reload_cache_{}", std::process::id()));
+        let cache = SnapshotCache::new(&dir);
+        let reloader = Reloader::new(
+            empty_store(),
+            DataSource::File(FileSource::new(&path)),
+            Arc::new(Metrics::new().unwrap()).data_set("default"),
+            Notifier::default(),
+            ReloadStatus::default(),
+            3,
+            Some(cache.clone()),
+        );
+
+        let outcome = reloader.reload().await.unwrap();
+        let cached = cache.load().await;
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let cached = cached.unwrap().unwrap();
+        assert_eq!(cached.stats().hash(), outcome.new_hash());
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::CacheError;
+use crate::listing;
+use crate::listing::{ListOptions, SortedOrders};
+use crate::types::data_set::DataSet;
+use crate::types::list_query::ListQuery;
+use brotli::enc::BrotliEncoderParams;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Arc;
+use warp::Filter;
+use warp::http::HeaderValue;
+use warp::http::header::{CONTENT_ENCODING, CONTENT_TYPE, VARY};
+use warp::hyper::body::Bytes;
+
+/// Brotli quality; 11 compresses slightly better but takes much longer.
+const BROTLI_QUALITY: i32 = 9;
+
+/// Content codings of the cached bodies, from most to least preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// Picks the coding with the highest quality the `Accept-Encoding` header
+    /// gives it, preferring br over gzip over identity at equal quality.
+    /// Codings with `q=0` are refused; `*` stands for any coding not listed.
+    pub(crate) fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let Some(accept_encoding) = accept_encoding else {
+            return Self::Identity;
+        };
+        let quality = |coding: &str| -> Option<f32> {
+            accept_encoding.split(',').find_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let name = parts.next()?;
+                if !name.eq_ignore_ascii_case(coding) {
+                    return None;
+                }
+                let q = parts
+                    .find_map(|parameter| parameter.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                Some(q)
+            })
+        };
+        // Identity is only weighed when listed or covered by `*`; it is the
+        // answer anyway if no other coding is accepted. Ties go to the earlier
+        // coding.
+        [
+            (Self::Brotli, "br"),
+            (Self::Gzip, "gzip"),
+            (Self::Identity, "identity"),
+        ]
+        .into_iter()
+        .filter_map(|(encoding, coding)| {
+            let q = quality(coding).or_else(|| quality("*"))?;
+            (q > 0.0).then_some((encoding, q))
+        })
+        .reduce(|best, next| if next.1 > best.1 { next } else { best })
+        .map_or(Self::Identity, |(encoding, _)| encoding)
+    }
+}
+
+/// Extracts the content coding to answer with from `Accept-Encoding`.
+pub(crate) fn accepted_encoding()
+-> impl Filter<Extract = (Encoding,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("accept-encoding")
+        .map(|accept_encoding: Option<String>| Encoding::negotiate(accept_encoding.as_deref()))
+}
+
+/// A JSON body, serialized once and compressed with every supported coding.
+#[derive(Debug, Clone)]
+pub(crate) struct CachedBody {
+    plain: Bytes,
+    gzip: Bytes,
+    brotli: Bytes,
+}
+
+impl CachedBody {
+    pub(crate) fn new<T: Serialize>(value: &T) -> Result<Self, CacheError> {
+        let plain = serde_json::to_vec(value).map_err(|e| CacheError::Encode(Arc::new(e)))?;
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&plain)
+            .map_err(|e| CacheError::Compress(Arc::new(e)))?;
+        let gzip = gzip
+            .finish()
+            .map_err(|e| CacheError::Compress(Arc::new(e)))?;
+
+        let mut brotli = Vec::new();
+        let params = BrotliEncoderParams {
+            quality: BROTLI_QUALITY,
+            ..BrotliEncoderParams::default()
+        };
+        brotli::BrotliCompress(&mut plain.as_slice(), &mut brotli, &params)
+            .map_err(|e| CacheError::Compress(Arc::new(e)))?;
+
+        Ok(Self {
+            plain: Bytes::from(plain),
+            gzip: Bytes::from(gzip),
+            brotli: Bytes::from(brotli),
+        })
+    }
+
+    /// Answers with the body in `encoding`. Only the reference count of the
+    /// shared buffer is incremented; nothing is serialized or compressed.
+    pub(crate) fn reply(&self, encoding: Encoding) -> warp::reply::Response {
+        let (body, coding) = match encoding {
+            Encoding::Brotli => (&self.brotli, Some("br")),
+            Encoding::Gzip => (&self.gzip, Some("gzip")),
+            Encoding::Identity => (&self.plain, None),
+        };
+        let mut response = warp::reply::Response::new(body.clone().into());
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+        if let Some(coding) = coding {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static(coding));
+        }
+        response
+    }
+}
+
+/// Bodies of the read-only routes without parameters, built once when a data
+/// set is swapped in: `/stats` and the first page of each list. Next to them,
+/// the sorted orders of the lists are kept as requests sort them.
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseCache {
+    stats: CachedBody,
+    assets: CachedBody,
+    exchanges: CachedBody,
+    instruments: CachedBody,
+    sorted: Arc<SortedOrders>,
+}
+
+impl ResponseCache {
+    pub(crate) fn build(data_set: &DataSet) -> Result<Self, CacheError> {
+        let options = ListOptions::parse(&ListQuery::default())?;
+        Ok(Self {
+            stats: CachedBody::new(data_set.stats())?,
+            assets: first_page(data_set, "assets", data_set.assets(), &options)?,
+            exchanges: first_page(data_set, "exchanges", data_set.exchanges(), &options)?,
+            instruments: first_page(
+                data_set,
+                "instruments",
+                &data_set.instruments(None, None),
+                &options,
+            )?,
+            sorted: Arc::default(),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> &CachedBody {
+        &self.stats
+    }
+    pub(crate) fn assets(&self) -> &CachedBody {
+        &self.assets
+    }
+    pub(crate) fn exchanges(&self) -> &CachedBody {
+        &self.exchanges
+    }
+    pub(crate) fn instruments(&self) -> &CachedBody {
+        &self.instruments
+    }
+    pub(crate) fn sorted(&self) -> &SortedOrders {
+        &self.sorted
+    }
+}
+
+fn first_page<T: Serialize>(
+    data_set: &DataSet,
+    list: &str,
+    records: &[T],
+    options: &ListOptions,
+) -> Result<CachedBody, CacheError> {
+    let page = listing::page(data_set, list, records, options, None)?;
+    CachedBody::new(&page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::Utc;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+    use warp::Reply;
+
+    fn data_set(instruments: usize) -> DataSet {
+        let instruments: Vec<_> = (0..instruments)
+            .map(|i| {
+                serde_json::json!({
+                    "id": format!("XNAS:I{}", i),
+                    "symbol": format!("I{}", i),
+                    "exchange_id": "XNAS",
+                    "base_asset": format!("A{}", i % 500),
+                    "quote_asset": "USD",
+                })
+            })
+            .collect();
+        let payload: Payload =
+            serde_json::from_value(serde_json::json!({ "instruments": instruments })).unwrap();
+        DataSet::from_payload(payload, Utc::now()).unwrap()
+    }
+
+    async fn body(response: warp::reply::Response) -> Vec<u8> {
+        warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn negotiates_the_preferred_accepted_coding() {
+        for (accept_encoding, expected) in [
+            (None, Encoding::Identity),
+            (Some("gzip, deflate, br"), Encoding::Brotli),
+            (Some("gzip"), Encoding::Gzip),
+            (Some("br;q=0, gzip;q=0.5"), Encoding::Gzip),
+            (Some("*"), Encoding::Brotli),
+            (Some("*, br;q=0"), Encoding::Gzip),
+            (Some("deflate"), Encoding::Identity),
+            (Some("GZIP;q=0"), Encoding::Identity),
+            (Some("gzip;q=1, br;q=0.1"), Encoding::Gzip),
+            (Some("br;q=0.5, gzip;q=0.5"), Encoding::Brotli),
+            (Some("identity, gzip;q=0.5"), Encoding::Identity),
+        ] {
+            assert_eq!(
+                Encoding::negotiate(accept_encoding),
+                expected,
+                "{:?}",
+                accept_encoding
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_bodies_decode_to_the_serialized_response() {
+        let data_set = data_set(3);
+        let responses = ResponseCache::build(&data_set).unwrap();
+        let expected = body(warp::reply::json(data_set.stats()).into_response()).await;
+
+        let plain = responses.stats().reply(Encoding::Identity);
+        assert!(plain.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(body(plain).await, expected);
+
+        let gzip = responses.stats().reply(Encoding::Gzip);
+        assert_eq!(gzip.headers()[CONTENT_ENCODING], "gzip");
+        let mut decoded = Vec::new();
+        GzDecoder::new(body(gzip).await.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, expected);
+
+        let brotli = responses.stats().reply(Encoding::Brotli);
+        assert_eq!(brotli.headers()[CONTENT_ENCODING], "br");
+        let mut decoded = Vec::new();
+        brotli::BrotliDecompress(&mut body(brotli).await.as_slice(), &mut decoded).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    /// Compares building responses per request with cloning the cached bodies.
+    ///
+    /// `bazel test //rest_tokio:bench` or `cargo test -- --ignored --nocapture bench_`
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_cached_responses() {
+        fn throughput<R>(name: &str, mut respond: impl FnMut() -> R) -> f64 {
+            let start = Instant::now();
+            let mut requests = 0u64;
+            while start.elapsed() < Duration::from_secs(1) {
+                std::hint::black_box(respond());
+                requests += 1;
+            }
+            let rate = requests as f64 / start.elapsed().as_secs_f64();
+            println!("{:<36} {:>12.0} responses/s", name, rate);
+            rate
+        }
+
+        let data_set = data_set(10_000);
+        let responses = ResponseCache::build(&data_set).unwrap();
+        let options = ListOptions::parse(&ListQuery::default()).unwrap();
+        let instruments = data_set.instruments(None, None);
+
+        let serialized = throughput("/stats serialized", || {
+            warp::reply::json(data_set.stats()).into_response()
+        });
+        let cached = throughput("/stats cached", || {
+            responses.stats().reply(Encoding::Identity)
+        });
+        println!("{:<36} {:>12.1}x", "/stats speedup", cached / serialized);
+
+        let serialized = throughput("/instruments serialized", || {
+            let page =
+                listing::page(&data_set, "instruments", &instruments, &options, None).unwrap();
+            warp::reply::json(&page).into_response()
+        });
+        let cached = throughput("/instruments cached", || {
+            responses.instruments().reply(Encoding::Identity)
+        });
+        println!(
+            "{:<36} {:>12.1}x",
+            "/instruments speedup",
+            cached / serialized
+        );
+
+        let compressed = throughput("/instruments serialized and gzipped", || {
+            let page =
+                listing::page(&data_set, "instruments", &instruments, &options, None).unwrap();
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            gzip.write_all(&serde_json::to_vec(&page).unwrap()).unwrap();
+            gzip.finish().unwrap()
+        });
+        let cached = throughput("/instruments cached gzip", || {
+            responses.instruments().reply(Encoding::Gzip)
+        });
+        println!(
+            "{:<36} {:>12.1}x",
+            "/instruments gzip speedup",
+            cached / compressed
+        );
+    }
+}
@@ -0,0 +1,434 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::access_log;
+use crate::access_log::AccessLog;
+use crate::auth;
+use crate::conditional;
+use crate::events;
+use crate::handler;
+use crate::handler::DataSets;
+use crate::registry::Registered;
+use crate::reload::Reloader;
+use crate::response_cache;
+use crate::service::Service;
+use crate::types::MetaDataStore;
+use crate::types::diff_query::DiffQuery;
+use crate::types::instrument_query::InstrumentQuery;
+use crate::types::list_query::ListQuery;
+use crate::types::rollback_query::RollbackQuery;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+use warp::Filter;
+use warp::filters::BoxedFilter;
+use warp::http::Method;
+
+/// A route of the service and the methods it answers.
+#[derive(Debug)]
+pub(crate) struct Route {
+    /// Path pattern; a `{...}` segment matches any single path segment.
+    pub(crate) path: &'static str,
+    pub(crate) methods: &'static [Method],
+}
+
+/// All routes of the service. Requests outside of this table are answered
+/// with `404` or `405`, and are counted as `unmatched` in the metrics to keep
+/// the label cardinality bounded.
+pub(crate) const ROUTES: &[Route] = &[
+    route("/health", &[Method::GET]),
+    route("/ready", &[Method::GET]),
+    route("/version", &[Method::GET]),
+    route("/stats", &[Method::GET]),
+    route("/stats/diff", &[Method::GET]),
+    route("/assets", &[Method::GET]),
+    route("/assets/{id}", &[Method::GET]),
+    route("/exchanges", &[Method::GET]),
+    route("/exchanges/{id}", &[Method::GET]),
+    route("/instruments", &[Method::GET]),
+    route("/datasets", &[Method::GET]),
+    route("/datasets/{name}/ready", &[Method::GET]),
+    route("/datasets/{name}/stats", &[Method::GET]),
+    route("/datasets/{name}/stats/diff", &[Method::GET]),
+    route("/datasets/{name}/assets", &[Method::GET]),
+    route("/datasets/{name}/assets/{id}", &[Method::GET]),
+    route("/datasets/{name}/exchanges", &[Method::GET]),
+    route("/datasets/{name}/exchanges/{id}", &[Method::GET]),
+    route("/datasets/{name}/instruments", &[Method::GET]),
+    route("/datasets/{name}/events", &[Method::GET]),
+    route("/datasets/{name}/ws", &[Method::GET]),
+    route("/events", &[Method::GET]),
+    route("/ws", &[Method::GET]),
+    route("/metrics", &[Method::GET]),
+    route("/admin/log-level", &[Method::GET, Method::PUT]),
+    route("/admin/reload", &[Method::POST]),
+    route("/admin/datasets/{name}/reload", &[Method::POST]),
+    route("/admin/schedule", &[Method::GET]),
+    route("/admin/snapshots", &[Method::GET]),
+    route("/admin/quarantine", &[Method::GET]),
+    route("/admin/rollback/{hash}", &[Method::POST]),
+    route("/admin/pin", &[Method::DELETE]),
+    route("/admin/datasets/{name}/schedule", &[Method::GET]),
+    route("/admin/datasets/{name}/snapshots", &[Method::GET]),
+    route("/admin/datasets/{name}/quarantine", &[Method::GET]),
+    route("/admin/datasets/{name}/rollback/{hash}", &[Method::POST]),
+    route("/admin/datasets/{name}/pin", &[Method::DELETE]),
+];
+
+/// Builds the read-only data routes over `store`: `/stats`, the records and
+/// their lists. Without a history, a cursor is only valid while the data set
+/// it was issued for is in `store`. The routes of a [`Service`] are built on
+/// top of these by [`build_service_routes`].
+pub fn build_routes(
+    store: MetaDataStore,
+) -> impl Filter<Extract = (impl warp::Reply + use<>,), Error = Infallible> + Clone + Send + Sync + use<>
+{
+    let data_sets = warp::any()
+        .map(move || store.clone() as Arc<dyn DataSets>)
+        .boxed();
+    read_routes(data_sets).recover(handler::handle_rejection)
+}
+
+/// Builds the routes of `service`. Each request runs in its own span, is
+/// recorded in the metrics and written to the access log.
+pub fn build_service_routes(
+    service: &Service,
+) -> impl Filter<Extract = (impl warp::Reply + use<>,), Error = Infallible> + Clone + Send + Sync + use<>
+{
+    let config = &service.config;
+    let with_log = {
+        let log = service.log.clone();
+        warp::any().map(move || log.clone())
+    };
+    let with_metrics = {
+        let metrics = service.metrics.clone();
+        warp::any().map(move || metrics.clone())
+    };
+    let with_default = {
+        let default = service.registry.default_data_set();
+        warp::any().map(move || default.clone())
+    };
+    let with_registry = {
+        let registry = service.registry.clone();
+        warp::any().map(move || registry.clone())
+    };
+
+    debug!("Build health route");
+    let health_check = warp::get()
+        .and(warp::path("health"))
+        .and(warp::path::end())
+        .and_then(handler::get_health_handler);
+
+    let get_version = warp::get()
+        .and(warp::path("version"))
+        .and(warp::path::end())
+        .and_then(handler::get_version_handler);
+
+    debug!("Build data routes");
+    let max_staleness = config.health.max_staleness();
+    let heartbeat = config.events.heartbeat();
+    let default_data_set = data_set_routes(with_default.clone().boxed(), max_staleness, heartbeat);
+
+    debug!("Build data set routes");
+    let get_datasets = warp::get()
+        .and(warp::path!("datasets"))
+        .and(with_registry.clone())
+        .and(conditional::preconditions())
+        .and_then(handler::get_datasets_handler);
+
+    let named = warp::path("datasets")
+        .and(warp::path::param::<String>())
+        .and(with_registry)
+        .and_then(handler::find_data_set);
+    let named_data_set = data_set_routes(named.clone().boxed(), max_staleness, heartbeat);
+
+    debug!("Build metrics route");
+    let get_metrics = warp::get()
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+        .and(with_metrics)
+        .and_then(handler::get_metrics_handler);
+
+    debug!("Build admin routes");
+    if config.admin.token.is_none() {
+        warn!("No admin token configured; admin routes are disabled");
+    }
+    let admin_auth = auth::bearer_token(config.admin.token.clone());
+
+    let get_log_filter = warp::get()
+        .and(warp::path!("admin" / "log-level"))
+        .and(admin_auth.clone())
+        .and(with_log.clone())
+        .and_then(handler::get_log_filter_handler);
+
+    let put_log_filter = warp::put()
+        .and(warp::path!("admin" / "log-level"))
+        .and(admin_auth.clone())
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::bytes())
+        .and(with_log.clone())
+        .and_then(handler::put_log_filter_handler);
+
+    let default_admin = admin_routes(
+        warp::path("admin").and(with_default).boxed(),
+        admin_auth.clone().boxed(),
+    );
+    let named_admin = admin_routes(
+        warp::path("admin").and(named).boxed(),
+        admin_auth.clone().boxed(),
+    );
+
+    // Answers unknown paths with 404 and known paths with 405 if none of the
+    // routes above accepted the method.
+    let fallback = warp::path::full()
+        .and(warp::method())
+        .and_then(handler::fallback_handler);
+
+    let metrics = service.metrics.clone();
+    let access_log = AccessLog::new(config.log.access);
+    let routes = health_check
+        .or(get_version)
+        .or(default_data_set)
+        .or(get_datasets)
+        .or(named_data_set)
+        .or(get_metrics)
+        .or(get_log_filter)
+        .or(put_log_filter)
+        .or(default_admin)
+        .or(named_admin)
+        .or(fallback)
+        .recover(handler::handle_rejection);
+    access_log::request()
+        .and(routes)
+        .map(move |request, reply| access_log.finish(request, reply))
+        .with(warp::log::custom(move |info| {
+            metrics.observe_request(
+                info.path(),
+                info.method().as_str(),
+                info.status().as_u16(),
+                info.elapsed(),
+            )
+        }))
+        .with(warp::trace(access_log::span))
+}
+
+/// Routes that read the data set extracted by `data_set` or stream its
+/// changes, below the path it matches: the default data set at the root, a
+/// named one below `/datasets/{name}`.
+fn data_set_routes(
+    data_set: BoxedFilter<(Arc<Registered>,)>,
+    max_staleness: Option<Duration>,
+    heartbeat: Duration,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone + Send + Sync {
+    let data_set = reloader_of(data_set);
+    let data_sets = data_set
+        .clone()
+        .map(|reloader: Arc<Reloader>| reloader as Arc<dyn DataSets>)
+        .boxed();
+
+    let ready_check = warp::get()
+        .and(data_set.clone())
+        .and(warp::path!("ready"))
+        .and(warp::any().map(move || max_staleness))
+        .and_then(handler::get_ready_handler);
+
+    let get_stats_diff = warp::get()
+        .and(data_set.clone())
+        .and(warp::path!("stats" / "diff"))
+        .and(warp::query::<DiffQuery>())
+        .and(conditional::preconditions())
+        .and_then(handler::get_stats_diff_handler);
+
+    let get_events = warp::get()
+        .and(data_set.clone())
+        .and(warp::path!("events"))
+        .and(events::last_event_id())
+        .and(warp::any().map(move || heartbeat))
+        .and_then(handler::get_events_handler);
+
+    let get_ws = warp::get()
+        .and(data_set)
+        .and(warp::path!("ws"))
+        .and(warp::ws())
+        .and(events::last_event_id())
+        .and(warp::any().map(move || heartbeat))
+        .and_then(handler::get_ws_handler);
+
+    ready_check
+        .or(read_routes(data_sets))
+        .or(get_stats_diff)
+        .or(get_events)
+        .or(get_ws)
+}
+
+/// Routes that read the active data set of `data_sets`, below the path it
+/// matches.
+fn read_routes(
+    data_sets: BoxedFilter<(Arc<dyn DataSets>,)>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone + Send + Sync {
+    let get_stats = warp::get()
+        .and(data_sets.clone())
+        .and(warp::path!("stats"))
+        .and(conditional::preconditions())
+        .and(response_cache::accepted_encoding())
+        .and_then(handler::get_stats_handler);
+
+    let get_assets = warp::get()
+        .and(data_sets.clone())
+        .and(warp::path!("assets"))
+        .and(warp::query::<ListQuery>())
+        .and(conditional::preconditions())
+        .and(response_cache::accepted_encoding())
+        .and_then(handler::get_assets_handler);
+
+    let get_asset = warp::get()
+        .and(data_sets.clone())
+        .and(warp::path!("assets" / String))
+        .and(conditional::preconditions())
+        .and_then(handler::get_asset_handler);
+
+    let get_exchanges = warp::get()
+        .and(data_sets.clone())
+        .and(warp::path!("exchanges"))
+        .and(warp::query::<ListQuery>())
+        .and(conditional::preconditions())
+        .and(response_cache::accepted_encoding())
+        .and_then(handler::get_exchanges_handler);
+
+    let get_exchange = warp::get()
+        .and(data_sets.clone())
+        .and(warp::path!("exchanges" / String))
+        .and(conditional::preconditions())
+        .and_then(handler::get_exchange_handler);
+
+    let get_instruments = warp::get()
+        .and(data_sets)
+        .and(warp::path!("instruments"))
+        .and(warp::query::<InstrumentQuery>())
+        .and(warp::query::<ListQuery>())
+        .and(conditional::preconditions())
+        .and(response_cache::accepted_encoding())
+        .and_then(handler::get_instruments_handler);
+
+    get_stats
+        .or(get_assets)
+        .or(get_asset)
+        .or(get_exchanges)
+        .or(get_exchange)
+        .or(get_instruments)
+}
+
+/// Admin routes of the data set extracted by `data_set`, below the path it
+/// matches: `/admin` for the default data set, `/admin/datasets/{name}` for a
+/// named one.
+fn admin_routes(
+    data_set: BoxedFilter<(Arc<Registered>,)>,
+    admin_auth: BoxedFilter<()>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone + Send + Sync {
+    let reloader = reloader_of(data_set.clone());
+
+    let post_reload = warp::post()
+        .and(reloader.clone())
+        .and(warp::path!("reload"))
+        .and(admin_auth.clone())
+        .and_then(handler::post_reload_handler);
+
+    let get_snapshots = warp::get()
+        .and(reloader.clone())
+        .and(warp::path!("snapshots"))
+        .and(admin_auth.clone())
+        .and_then(handler::get_snapshots_handler);
+
+    let get_quarantine = warp::get()
+        .and(reloader.clone())
+        .and(warp::path!("quarantine"))
+        .and(admin_auth.clone())
+        .and_then(handler::get_quarantine_handler);
+
+    let post_rollback = warp::post()
+        .and(reloader.clone())
+        .and(warp::path!("rollback" / String))
+        .and(admin_auth.clone())
+        .and(warp::query::<RollbackQuery>())
+        .and_then(handler::post_rollback_handler);
+
+    let delete_pin = warp::delete()
+        .and(reloader)
+        .and(warp::path!("pin"))
+        .and(admin_auth.clone())
+        .and_then(handler::delete_pin_handler);
+
+    let get_schedule = warp::get()
+        .and(data_set)
+        .and(warp::path!("schedule"))
+        .and(admin_auth)
+        .and_then(handler::get_schedule_handler);
+
+    post_reload
+        .or(get_snapshots)
+        .or(get_quarantine)
+        .or(post_rollback)
+        .or(delete_pin)
+        .or(get_schedule)
+}
+
+fn reloader_of(data_set: BoxedFilter<(Arc<Registered>,)>) -> BoxedFilter<(Arc<Reloader>,)> {
+    data_set
+        .map(|data_set: Arc<Registered>| data_set.reloader().clone())
+        .boxed()
+}
+
+const fn route(path: &'static str, methods: &'static [Method]) -> Route {
+    Route { path, methods }
+}
+
+/// Finds the route matching `path`.
+pub(crate) fn find(path: &str) -> Option<&'static Route> {
+    ROUTES.iter().find(|route| matches_route(route.path, path))
+}
+
+fn matches_route(route: &str, path: &str) -> bool {
+    let mut route = route.split('/');
+    let mut path = path.split('/');
+    loop {
+        match (route.next(), path.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(actual)) => {
+                let placeholder = expected.starts_with('{') && !actual.is_empty();
+                if !placeholder && expected != actual {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_matches_placeholders_and_whole_paths() {
+        let path = |path| find(path).map(|route| route.path);
+
+        assert_eq!(path("/stats"), Some("/stats"));
+        assert_eq!(path("/assets/BTC"), Some("/assets/{id}"));
+        assert_eq!(path("/assets/"), None);
+        assert_eq!(path("/assets/BTC/extra"), None);
+        assert_eq!(path("/does/not/exist"), None);
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::clock::Clock;
+use crate::config::ReloadConfig;
+use crate::errors::ConfigError;
+use crate::reload::Reloader;
+use chrono::{DateTime, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::Rng;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{Instrument, Span, debug, info, info_span, warn};
+
+/// Cron schedule of the data reload, evaluated in an IANA timezone.
+///
+/// Run times follow the local wall clock, so a daily run at 01:00 stays at 01:00
+/// across DST changes. A run that falls into the hour skipped in spring starts
+/// once the clock has jumped forward. A run in the hour repeated in autumn
+/// starts once, on its first occurrence.
+///
+/// A failed run is retried with exponential backoff a bounded number of times.
+#[derive(Debug, Clone)]
+pub(crate) struct ReloadSchedule {
+    expression: String,
+    schedule: cron::Schedule,
+    timezone: Tz,
+    jitter: Duration,
+    retry_attempts: u32,
+    retry_backoff: Duration,
+    retry_max_backoff: Duration,
+}
+
+impl ReloadSchedule {
+    pub(crate) fn from_config(config: &ReloadConfig) -> Result<Self, ConfigError> {
+        let schedule =
+            cron::Schedule::from_str(&config.schedule).map_err(|e| ConfigError::Schedule {
+                expression: config.schedule.clone(),
+                source: Arc::new(e),
+            })?;
+        let timezone = Tz::from_str(&config.timezone).map_err(|e| ConfigError::Timezone {
+            timezone: config.timezone.clone(),
+            source: Arc::new(e),
+        })?;
+        Ok(Self {
+            expression: config.schedule.clone(),
+            schedule,
+            timezone,
+            jitter: Duration::from_secs(config.jitter_secs),
+            retry_attempts: config.retry_attempts,
+            retry_backoff: Duration::from_secs(config.retry_backoff_secs),
+            retry_max_backoff: Duration::from_secs(config.retry_max_backoff_secs),
+        })
+    }
+
+    pub(crate) fn expression(&self) -> &str {
+        &self.expression
+    }
+    pub(crate) fn timezone(&self) -> Tz {
+        self.timezone
+    }
+    pub(crate) fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Planned run times after `after`, before jitter is added.
+    pub(crate) fn upcoming(&self, after: DateTime<Utc>) -> impl Iterator<Item = DateTime<Tz>> + '_ {
+        let timezone = self.timezone;
+        // The cron iterator walks the local wall clock, represented as UTC.
+        let wall_clock = Utc.from_utc_datetime(&after.with_timezone(&timezone).naive_local());
+        let mut last = after;
+        self.schedule
+            .after(&wall_clock)
+            .map(move |local| resolve(&timezone, local.naive_utc()))
+            // Wall clock times that map to an instant already passed are dropped.
+            .filter(move |run| {
+                let run = run.with_timezone(&Utc);
+                let later = run > last;
+                if later {
+                    last = run;
+                }
+                later
+            })
+    }
+
+    /// Picks a random delay of up to the configured jitter.
+    fn random_jitter(&self) -> Duration {
+        random_up_to(self.jitter)
+    }
+
+    /// Delay before the given retry, counting from 0.
+    ///
+    /// The delay doubles with every retry up to the maximum. Half of it is
+    /// random, so that replicas that failed together do not retry together.
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .retry_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.retry_max_backoff);
+        delay / 2 + random_up_to(delay / 2)
+    }
+}
+
+fn random_up_to(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let millis = rand::thread_rng().gen_range(0..=max.as_millis() as u64);
+    Duration::from_millis(millis)
+}
+
+/// Maps a wall clock time in `timezone` to an instant.
+fn resolve(timezone: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) => time,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => {
+            // Skipped by a DST change: apply the offset from before the change,
+            // which moves the run forward by the length of the gap.
+            let before = timezone
+                .offset_from_utc_datetime(&(local - chrono::Duration::days(1)))
+                .fix()
+                .local_minus_utc();
+            timezone.from_utc_datetime(&(local - chrono::Duration::seconds(before.into())))
+        }
+    }
+}
+
+/// Runs the reloads on the schedule until it is shut down.
+pub(crate) struct Scheduler {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Scheduler {
+    /// Starts the reloads; their spans are children of the current span.
+    ///
+    /// Runs are planned and waited for on the clock of the reloader.
+    pub(crate) fn start(schedule: Arc<ReloadSchedule>, reloader: Arc<Reloader>) -> Self {
+        let (stop, mut stopped) = oneshot::channel::<()>();
+        let parent = Span::current();
+        let clock = reloader.clock().clone();
+        // Plan the first run from the start, even if the task is polled later.
+        let mut after = clock.now();
+        let task = tokio::spawn(async move {
+            loop {
+                let Some(next) = schedule.upcoming(after).next() else {
+                    info!(
+                        schedule = schedule.expression(),
+                        "No further reloads scheduled"
+                    );
+                    break;
+                };
+                let jitter = schedule.random_jitter();
+                debug!(%next, ?jitter, "Next reload scheduled");
+
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = clock.sleep_until(next.with_timezone(&Utc) + jitter) => {}
+                }
+
+                let stopped_during_retry =
+                    reload_with_retries(&schedule, &reloader, clock.as_ref(), &mut stopped)
+                        .instrument(info_span!(parent: &parent, "reload", scheduled = %next))
+                        .await;
+                if stopped_during_retry {
+                    break;
+                }
+                // Runs missed while reloading are skipped, and no run is
+                // planned before the previous one.
+                after = clock.now().max(next.with_timezone(&Utc));
+            }
+        });
+        Self { stop, task }
+    }
+
+    /// Stops scheduling reloads and waits for a running reload to finish.
+    pub(crate) async fn shutdown(self) {
+        let _ = self.stop.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Reloads, retrying failures with backoff. Returns `true` if the scheduler
+/// was stopped while waiting for a retry.
+async fn reload_with_retries(
+    schedule: &ReloadSchedule,
+    reloader: &Reloader,
+    clock: &dyn Clock,
+    stopped: &mut oneshot::Receiver<()>,
+) -> bool {
+    let mut retry = 0;
+    // Errors are logged and counted by the reloader.
+    while reloader.reload().await.is_err() && retry < schedule.retry_attempts {
+        let backoff = schedule.backoff(retry);
+        retry += 1;
+        warn!(
+            retry,
+            of = schedule.retry_attempts,
+            ?backoff,
+            "Retry failed reload"
+        );
+        tokio::select! {
+            _ = &mut *stopped => return true,
+            _ = clock.sleep_until(clock.now() + backoff) => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(expression: &str, timezone: &str) -> ReloadSchedule {
+        ReloadSchedule::from_config(&ReloadConfig {
+            schedule: expression.to_string(),
+            timezone: timezone.to_string(),
+            ..ReloadConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn runs(schedule: &ReloadSchedule, after: &str, n: usize) -> Vec<DateTime<Utc>> {
+        schedule
+            .upcoming(utc(after))
+            .take(n)
+            .map(|run| run.with_timezone(&Utc))
+            .collect()
+    }
+
+    #[test]
+    fn daily_run_keeps_local_time_across_dst() {
+        let schedule = schedule("0 0 1 * * *", "Europe/Berlin");
+
+        assert_eq!(
+            runs(&schedule, "2024-03-30T12:00:00Z", 2),
+            vec![utc("2024-03-31T00:00:00Z"), utc("2024-03-31T23:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn run_in_skipped_hour_moves_forward() {
+        let schedule = schedule("0 30 2 * * *", "America/New_York");
+
+        assert_eq!(
+            runs(&schedule, "2024-03-09T12:00:00Z", 2),
+            vec![
+                utc("2024-03-10T03:30:00-04:00"),
+                utc("2024-03-11T02:30:00-04:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn run_in_repeated_hour_runs_once() {
+        let schedule = schedule("0 30 1 * * *", "America/New_York");
+
+        assert_eq!(
+            runs(&schedule, "2024-11-03T00:00:00Z", 2),
+            vec![
+                utc("2024-11-03T01:30:00-04:00"),
+                utc("2024-11-04T01:30:00-05:00"),
+            ]
+        );
+        // The second pass through 01:30 after the clocks went back is skipped.
+        assert_eq!(
+            runs(&schedule, "2024-11-03T01:10:00-05:00", 1),
+            vec![utc("2024-11-04T01:30:00-05:00")]
+        );
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let schedule = ReloadSchedule::from_config(&ReloadConfig {
+            jitter_secs: 2,
+            ..ReloadConfig::default()
+        })
+        .unwrap();
+
+        assert!((0..100).all(|_| schedule.random_jitter() <= Duration::from_secs(2)));
+        assert_eq!(
+            self::schedule("0 0 1 * * *", "UTC").random_jitter(),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let schedule = ReloadSchedule::from_config(&ReloadConfig {
+            retry_backoff_secs: 10,
+            retry_max_backoff_secs: 60,
+            ..ReloadConfig::default()
+        })
+        .unwrap();
+
+        for (retry, max) in [(0, 10), (1, 20), (2, 40), (3, 60), (31, 60), (100, 60)] {
+            let backoff = schedule.backoff(retry);
+            let max = Duration::from_secs(max);
+            assert!(
+                backoff >= max / 2 && backoff <= max,
+                "retry {}: {:?}",
+                retry,
+                backoff
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_timezone() {
+        let config = ReloadConfig {
+            timezone: String::from("EST+4"),
+            ..ReloadConfig::default()
+        };
+
+        assert!(ReloadSchedule::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::cache::SnapshotCache;
+use crate::clock::{SharedClock, SystemClock};
+use crate::config::Config;
+use crate::errors;
+use crate::errors::LoadError;
+use crate::logging::LogHandle;
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
+use crate::registry::{self, Registry};
+use crate::reload::{Reloader, run_init};
+use crate::schedule::{ReloadSchedule, Scheduler};
+use crate::source::DataSource;
+use crate::types::MetaDataStore;
+use crate::types::active_data_set::ActiveDataSet;
+use crate::types::data_set::DataSet;
+use crate::types::reload_status::ReloadStatus;
+use crate::validate::Validator;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+/// The running service: the data sets, their reload schedules and the state
+/// the routes share. Built with [`Service::start`], served with
+/// [`build_service_routes`](crate::build_service_routes).
+pub struct Service {
+    pub(crate) config: Config,
+    pub(crate) log: LogHandle,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) registry: Arc<Registry>,
+}
+
+impl Service {
+    /// Loads the initial data and starts the reload schedulers.
+    pub async fn start(config: &Config, log: LogHandle) -> Self {
+        debug!(source = %config.data.source, "Select data source");
+        let data_source =
+            DataSource::from_location(&config.data.source).expect("Failed to select data source");
+        Self::with_source(config, log, data_source, Arc::new(SystemClock)).await
+    }
+
+    /// Starts the service on `data_source` instead of `data.source`, with the
+    /// times of loads, schedules and readiness taken from `clock`.
+    pub(crate) async fn with_source(
+        config: &Config,
+        log: LogHandle,
+        data_source: DataSource,
+        clock: SharedClock,
+    ) -> Self {
+        debug!("Build notifier");
+        let notifier = Notifier::from_config(&config.notify).expect("Failed to build notifier");
+
+        debug!("Register metrics");
+        let metrics = Arc::new(Metrics::new().expect("Failed to register metrics"));
+        let default_metrics = metrics.data_set(registry::DEFAULT);
+
+        let cache = config.data.cache_dir.as_deref().map(SnapshotCache::new);
+
+        // A failed initial load is not fatal: the service starts with the cached
+        // data, or without data and reports not-ready, and picks up the live data
+        // with the next successful reload.
+        debug!("Load data");
+        let validator = Validator::from_config(&config.validation);
+        let mut status = ReloadStatus::default();
+        // A rejected data set is quarantined once the reloader exists.
+        let mut rejected = None;
+        let initial = match run_init(&data_source, clock.as_ref()).await {
+            Ok(meta_data) => {
+                let violations = validator.validate(&DataSet::default(), &meta_data);
+                if violations.is_empty() {
+                    Ok(meta_data)
+                } else {
+                    let e = LoadError::Rejected {
+                        hash: meta_data.stats().hash().to_string(),
+                        violations: violations.clone(),
+                    };
+                    rejected = Some((meta_data, violations));
+                    Err(e)
+                }
+            }
+            Err(e) => Err(e),
+        };
+        let meta_data = match initial {
+            Ok(meta_data) => {
+                info!(hash = %meta_data.stats().hash(), "Initial data loaded");
+                status.record_success(clock.now());
+                default_metrics.data_set_swapped(meta_data.stats().hash());
+                let cached = match &cache {
+                    Some(cache) => cache.store(&meta_data).await,
+                    None => Ok(()),
+                };
+                if let Err(e) = cached {
+                    warn!(error = %errors::report(&e), "Failed to cache data set");
+                }
+                meta_data
+            }
+            Err(e) => {
+                let e = errors::report(&e);
+                status.record_failure(clock.now(), e.clone());
+                let cached = match &cache {
+                    Some(cache) => cache.restore().await,
+                    None => None,
+                };
+                match cached {
+                    Some(meta_data) => {
+                        warn!(
+                            error = %e,
+                            hash = %meta_data.stats().hash(),
+                            downloaded = meta_data.stats().download_timestamp(),
+                            "Failed to load initial data; serving cached data until the next reload"
+                        );
+                        status.record_cache_fallback(
+                            meta_data.stats().download_timestamp().to_string(),
+                        );
+                        default_metrics.data_set_swapped(meta_data.stats().hash());
+                        meta_data
+                    }
+                    None => {
+                        error!(error = %e, "Failed to load initial data; not ready until the next reload");
+                        DataSet::default()
+                    }
+                }
+            }
+        };
+
+        debug!("Build meta-data store");
+        // ArcSwap hot-swaps data in a multi-threaded runtime.
+        // https://docs.rs/arc-swap/1.7.1/arc_swap/index.html
+        let store: MetaDataStore = Arc::new(ArcSwap::from_pointee(
+            ActiveDataSet::prepare(Arc::new(meta_data), clock.now()).await,
+        ));
+        let reloader = Arc::new(
+            Reloader::new(
+                store,
+                data_source,
+                default_metrics,
+                notifier.clone(),
+                status,
+                config.data.history_size,
+                cache,
+            )
+            .with_validator(validator)
+            .with_clock(clock),
+        );
+        if let Some((data_set, violations)) = rejected {
+            reloader.quarantine(data_set, violations).await;
+        }
+
+        // Run an async update on the configured schedule, by default every day at 01:00 UTC.
+        // The schedule follows the wall clock of its timezone, including DST changes.
+        let schedule = Arc::new(
+            ReloadSchedule::from_config(&config.reload).expect("Failed to build reload schedule"),
+        );
+        debug!(
+            schedule = schedule.expression(),
+            timezone = %schedule.timezone(),
+            jitter = ?schedule.jitter(),
+            "Start scheduler"
+        );
+        let scheduler = Scheduler::start(schedule.clone(), reloader.clone());
+
+        debug!(count = config.datasets.len(), "Start named data sets");
+        let registry = Arc::new(Registry::new(
+            config.data.source.clone(),
+            reloader,
+            schedule,
+            scheduler,
+            metrics.clone(),
+            notifier,
+        ));
+        registry.apply(config).await;
+
+        Self {
+            config: config.clone(),
+            log,
+            metrics,
+            registry,
+        }
+    }
+
+    /// Ends the event streams and stops scheduling reloads, waiting for
+    /// running reloads to finish.
+    pub async fn shutdown(&self) {
+        self.registry.shutdown().await;
+    }
+}
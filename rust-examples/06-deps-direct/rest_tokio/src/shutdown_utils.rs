@@ -0,0 +1,69 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use tracing::info;
+
+/// Registers a signal handler that waits for a signal that indicates a shutdown request.
+// https://stackoverflow.com/questions/77585473/rust-tokio-how-to-handle-more-signals-than-just-sigint-i-e-sigquit?noredirect=1#comment136778587_77585473
+pub async fn signal_handler(svc: &str) {
+    wait_for_signal_impl(svc).await
+}
+
+/// Waits for a signal that requests a graceful shutdown. Supports the following signals on unix:
+/// * SIGTERM
+/// * SIGINT (Ctrl-C)
+/// * SIGQUIT
+///
+/// SIGHUP does not shut down the service; it reloads the data sets from the config.
+#[cfg(unix)]
+async fn wait_for_signal_impl(svc: &str) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    // Docs: https://www.gnu.org/software/libc/manual/html_node/Termination-Signals.html
+    let mut signal_terminate = signal(SignalKind::terminate()).unwrap();
+    let mut signal_interrupt = signal(SignalKind::interrupt()).unwrap();
+    let mut signal_quit = signal(SignalKind::quit()).unwrap();
+
+    // https://docs.rs/tokio/latest/tokio/macro.select.html
+    tokio::select! {
+        _ = signal_terminate.recv() => info!("{svc} received SIGTERM"),
+        _ = signal_interrupt.recv() => info!("{svc} received SIGINT"),
+        _ = signal_quit.recv() => info!("{svc} received SIGQUIT"),
+    }
+}
+
+/// Waits for a signal that requests a graceful shutdown. Supports the following signals on Windows:
+/// * ctrl_c
+/// * ctrl_break
+/// * ctrl_close
+/// * ctrl_shutdown
+#[cfg(windows)]
+async fn wait_for_signal_impl(svc: &str) {
+    use tokio::signal::windows;
+
+    // Docs: https://learn.microsoft.com/en-us/windows/console/handlerroutine
+    let mut signal_c = windows::ctrl_c().unwrap();
+    let mut signal_break = windows::ctrl_break().unwrap();
+    let mut signal_close = windows::ctrl_close().unwrap();
+    let mut signal_shutdown = windows::ctrl_shutdown().unwrap();
+
+    // https://docs.rs/tokio/latest/tokio/macro.select.html
+    tokio::select! {
+        _ = signal_c.recv() => info!("{svc} received CTRL_C."),
+        _ = signal_break.recv() => info!("{svc} received CTRL_BREAK."),
+        _ = signal_close.recv() => info!("{svc} received CTRL_CLOSE."),
+        _ = signal_shutdown.recv() => info!("{svc} received CTRL_SHUTDOWN."),
+    }
+}
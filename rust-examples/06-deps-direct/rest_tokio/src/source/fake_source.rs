@@ -0,0 +1,66 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::LoadError;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// In-memory payload for tests. Clones share the payload, so a test can
+/// change what the service loads next.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FakeSource {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The payload served, or the error message of a failing source.
+    payload: Result<Vec<u8>, String>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            payload: Ok(b"{}".to_vec()),
+        }
+    }
+}
+
+impl FakeSource {
+    pub(crate) fn new(json: &str) -> Self {
+        let source = Self::default();
+        source.set(json);
+        source
+    }
+
+    /// Serves `json` from the next fetch on.
+    pub(crate) fn set(&self, json: &str) {
+        self.state.lock().unwrap().payload = Ok(json.as_bytes().to_vec());
+    }
+
+    /// Fails every fetch with `message` until the next `set`.
+    pub(crate) fn fail(&self, message: &str) {
+        self.state.lock().unwrap().payload = Err(message.to_string());
+    }
+
+    pub(crate) async fn fetch(&self) -> Result<Vec<u8>, LoadError> {
+        let state = self.state.lock().unwrap();
+        state.payload.clone().map_err(|message| LoadError::Io {
+            path: PathBuf::from("fake"),
+            source: Arc::new(io::Error::other(message)),
+        })
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::LoadError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Reads the payload from a file on the local file system.
+#[derive(Debug, Clone)]
+pub(crate) struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) async fn fetch(&self) -> Result<Vec<u8>, LoadError> {
+        tokio::fs::read(&self.path)
+            .await
+            .map_err(|e| LoadError::Io {
+                path: self.path.clone(),
+                source: Arc::new(e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::DataSource;
+
+    #[tokio::test]
+    async fn fetch_reads_payload_from_disk() {
+        let path = std::env::temp_dir().join(format!("file_source_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"assets": [{"id": "BTC"}], "exchanges": [], "instruments": []}"#,
+        )
+        .unwrap();
+
+        let source = DataSource::File(FileSource::new(&path));
+        let payload = source.fetch().await;
+        std::fs::remove_file(&path).unwrap();
+
+        let payload = payload.unwrap();
+        assert_eq!(payload.assets.len(), 1);
+        assert!(payload.exchanges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_fails_for_missing_file() {
+        let source = FileSource::new("/does/not/exist.json");
+        assert!(source.fetch().await.is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::LoadError;
+use std::time::Duration;
+
+/// Upper bound for a single download, including connecting and reading the body.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Downloads the payload from an HTTP(S) endpoint.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpSource {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpSource {
+    pub(crate) fn new(url: &str) -> Result<Self, LoadError> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| LoadError::from_http(url, e))?;
+
+        Ok(Self {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(crate) async fn fetch(&self) -> Result<Vec<u8>, LoadError> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| LoadError::from_http(&self.url, e))?;
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| LoadError::from_http(&self.url, e))?;
+
+        Ok(body.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::DataSource;
+    use std::net::SocketAddr;
+    use warp::Filter;
+    use warp::http::StatusCode;
+
+    /// Serves `body` with `status` on an ephemeral local port.
+    fn serve(status: StatusCode, body: &'static str) -> SocketAddr {
+        let route = warp::path("data.json").map(move || warp::reply::with_status(body, status));
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn fetch_downloads_payload() {
+        let addr = serve(
+            StatusCode::OK,
+            r#"{"assets": [], "exchanges": [{"id": "XNAS"}], "instruments": [{"id": "XNAS:AAPL"}]}"#,
+        );
+
+        let source = DataSource::from_location(&format!("http://{}/data.json", addr)).unwrap();
+        let payload = source.fetch().await.unwrap();
+
+        assert!(payload.assets.is_empty());
+        assert_eq!(payload.exchanges.len(), 1);
+        assert_eq!(payload.instruments.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_fails_on_error_status() {
+        let addr = serve(StatusCode::SERVICE_UNAVAILABLE, "maintenance");
+
+        let source = HttpSource::new(&format!("http://{}/data.json", addr)).unwrap();
+        assert!(source.fetch().await.is_err());
+    }
+}
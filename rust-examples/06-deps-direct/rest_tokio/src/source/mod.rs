@@ -0,0 +1,131 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors::LoadError;
+use crate::types::payload::Payload;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+#[cfg(test)]
+pub(crate) mod fake_source;
+pub(crate) mod file_source;
+pub(crate) mod http_source;
+
+#[cfg(test)]
+pub(crate) use crate::source::fake_source::FakeSource;
+pub(crate) use crate::source::file_source::FileSource;
+pub(crate) use crate::source::http_source::HttpSource;
+
+/// Name of the location that selects the sample data bundled with the binary.
+pub(crate) const BUILTIN: &str = "builtin";
+
+const BUILTIN_PAYLOAD: &[u8] = include_bytes!("../../data/sample.json");
+
+/// Upstream the service loads its reference data from.
+#[derive(Debug, Clone)]
+pub(crate) enum DataSource {
+    /// Sample data compiled into the binary.
+    Builtin,
+    /// JSON document on the local file system.
+    File(FileSource),
+    /// JSON document served over HTTP(S).
+    Http(HttpSource),
+    /// In-memory payload that tests can change.
+    #[cfg(test)]
+    Fake(FakeSource),
+}
+
+impl DataSource {
+    /// Selects a data source from a location string:
+    /// * `builtin` loads the bundled sample data
+    /// * `http://` and `https://` URLs are fetched over the network
+    /// * everything else, optionally prefixed with `file://`, is read from disk
+    pub(crate) fn from_location(location: &str) -> Result<Self, LoadError> {
+        let location = location.trim();
+        if location.is_empty() {
+            return Err(LoadError::Location(String::from(
+                "Data source location must not be empty",
+            )));
+        }
+
+        if location == BUILTIN {
+            Ok(Self::Builtin)
+        } else if location.starts_with("http://") || location.starts_with("https://") {
+            Ok(Self::Http(HttpSource::new(location)?))
+        } else {
+            let path = location.strip_prefix("file://").unwrap_or(location);
+            Ok(Self::File(FileSource::new(path)))
+        }
+    }
+
+    /// Fetches and decodes the current payload from the source.
+    pub(crate) async fn fetch(&self) -> Result<Payload, LoadError> {
+        let bytes = match self {
+            Self::Builtin => BUILTIN_PAYLOAD.to_vec(),
+            Self::File(source) => source.fetch().await?,
+            Self::Http(source) => source.fetch().await?,
+            #[cfg(test)]
+            Self::Fake(source) => source.fetch().await?,
+        };
+
+        serde_json::from_slice(&bytes).map_err(|e| LoadError::Decode {
+            location: self.to_string(),
+            source: Arc::new(e),
+        })
+    }
+}
+
+impl Display for DataSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Builtin => write!(f, "{}", BUILTIN),
+            Self::File(source) => write!(f, "{}", source.path().display()),
+            Self::Http(source) => write!(f, "{}", source.url()),
+            #[cfg(test)]
+            Self::Fake(_) => write!(f, "fake"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_location_selects_source() {
+        assert!(matches!(
+            DataSource::from_location("builtin"),
+            Ok(DataSource::Builtin)
+        ));
+        assert!(matches!(
+            DataSource::from_location("https://example.com/data.json"),
+            Ok(DataSource::Http(_))
+        ));
+        assert!(matches!(
+            DataSource::from_location("file:///tmp/data.json"),
+            Ok(DataSource::File(source)) if source.path().to_str() == Some("/tmp/data.json")
+        ));
+        assert!(DataSource::from_location(" ").is_err());
+    }
+
+    #[tokio::test]
+    async fn builtin_payload_decodes() {
+        let payload = DataSource::Builtin.fetch().await.unwrap();
+        assert!(!payload.assets.is_empty());
+        assert!(!payload.exchanges.is_empty());
+        assert!(!payload.instruments.is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::errors;
+use crate::response_cache::ResponseCache;
+use crate::types::data_set::DataSet;
+use chrono::{DateTime, Utc};
+use std::ops::Deref;
+use std::sync::Arc;
+use tracing::warn;
+
+/// The data set in the store, the time it was swapped in and the response
+/// bodies prepared for it.
+///
+/// The swap time is the `Last-Modified` time of everything served from the
+/// data set. It differs from the download time after a rollback.
+#[derive(Debug, Clone)]
+pub struct ActiveDataSet {
+    data_set: Arc<DataSet>,
+    swapped_at: DateTime<Utc>,
+    responses: Option<ResponseCache>,
+}
+
+impl ActiveDataSet {
+    /// Prepares the response bodies. If that fails, the handlers serialize
+    /// every response instead.
+    pub fn new(data_set: Arc<DataSet>, swapped_at: DateTime<Utc>) -> Self {
+        let responses = ResponseCache::build(&data_set)
+            .inspect_err(|e| warn!(error = %errors::report(e), "Failed to prepare response bodies"))
+            .ok();
+        Self {
+            data_set,
+            swapped_at,
+            responses,
+        }
+    }
+    /// Like [`ActiveDataSet::new`], but prepares the response bodies on the
+    /// blocking pool. Compressing a large data set takes long enough to stall
+    /// the requests and event streams of a runtime worker.
+    pub(crate) async fn prepare(data_set: Arc<DataSet>, swapped_at: DateTime<Utc>) -> Self {
+        let blocking = data_set.clone();
+        match tokio::task::spawn_blocking(move || Self::new(blocking, swapped_at)).await {
+            Ok(active) => active,
+            Err(e) => {
+                warn!(error = %e, "Failed to prepare response bodies");
+                Self {
+                    data_set,
+                    swapped_at,
+                    responses: None,
+                }
+            }
+        }
+    }
+    pub fn data_set(&self) -> &Arc<DataSet> {
+        &self.data_set
+    }
+    pub fn swapped_at(&self) -> DateTime<Utc> {
+        self.swapped_at
+    }
+    pub(crate) fn responses(&self) -> Option<&ResponseCache> {
+        self.responses.as_ref()
+    }
+}
+
+impl Deref for ActiveDataSet {
+    type Target = DataSet;
+
+    fn deref(&self) -> &DataSet {
+        &self.data_set
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Version and build details of the running binary, as served at `/version`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    version: String,
+    git_commit: String,
+    build_timestamp: String,
+    rustc_version: String,
+    profile: String,
+}
+
+impl BuildInfo {
+    pub fn new(
+        version: String,
+        git_commit: String,
+        build_timestamp: String,
+        rustc_version: String,
+        profile: String,
+    ) -> Self {
+        Self {
+            version,
+            git_commit,
+            build_timestamp,
+            rustc_version,
+            profile,
+        }
+    }
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+    pub fn git_commit(&self) -> &str {
+        &self.git_commit
+    }
+    pub fn build_timestamp(&self) -> &str {
+        &self.build_timestamp
+    }
+    pub fn rustc_version(&self) -> &str {
+        &self.rustc_version
+    }
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+}
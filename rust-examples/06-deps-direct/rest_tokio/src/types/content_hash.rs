@@ -0,0 +1,105 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::Serialize;
+use serde_json::Value;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Computes a stable 64-bit hash over the canonical JSON form of `value`.
+///
+/// The canonical form sorts object keys and drops insignificant whitespace, so
+/// the hash only changes when the content changes, independent of the field
+/// order the upstream used. FNV-1a is used because, unlike the std hasher, its
+/// output is specified and therefore stable across Rust releases and processes.
+pub fn content_hash<T: Serialize>(value: &T) -> Result<u64, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut canonical = Vec::new();
+    write_canonical(&value, &mut canonical)?;
+    Ok(fnv1a(&canonical))
+}
+
+/// Formats a content hash the way it is published in `Stats`.
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+
+            out.push(b'{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical(item, out)?;
+            }
+            out.push(b'}');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn hash_ignores_key_order_and_whitespace() {
+        let a: Value =
+            serde_json::from_str(r#"{"id": "BTC", "meta": {"x": 1, "y": [1, 2]}}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"meta":{"y":[1,2],"x":1},"id":"BTC"}"#).unwrap();
+
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+    }
+
+    #[test]
+    fn hash_changes_with_content() {
+        let a: Value = serde_json::from_str(r#"{"id": "BTC", "items": [1, 2]}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"id": "BTC", "items": [2, 1]}"#).unwrap();
+
+        assert_ne!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+    }
+}
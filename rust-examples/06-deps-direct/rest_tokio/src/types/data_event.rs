@@ -0,0 +1,51 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::stats::Stats;
+use serde::{Deserialize, Serialize};
+
+/// Message pushed to the `/events` and `/ws` subscribers.
+///
+/// Ids increase with every change, so a client that reconnects with the id
+/// of the last event it saw receives the events it missed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DataEvent {
+    /// A new data set was swapped in.
+    Change {
+        id: u64,
+        old_hash: String,
+        new_hash: String,
+        stats: Stats,
+    },
+    /// Events were missed and cannot be replayed. `stats` is the active data
+    /// set; the client should refetch what it caches.
+    Resync { id: u64, stats: Stats },
+}
+
+impl DataEvent {
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Change { id, .. } | Self::Resync { id, .. } => *id,
+        }
+    }
+    /// Event name on the SSE stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Change { .. } => "change",
+            Self::Resync { .. } => "resync",
+        }
+    }
+}
@@ -13,20 +13,323 @@
 // limitations under the License.
 
 
+use crate::errors::LoadError;
+use crate::types::asset::Asset;
+use crate::types::content_hash;
+use crate::types::exchange::Exchange;
+use crate::types::instrument::Instrument;
+use crate::types::payload::Payload;
 use crate::types::stats::Stats;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredDataSet")]
 pub struct DataSet {
     stats: Stats,
     hash: u64,
+    payload: Payload,
+    /// Derived from the payload, so it is rebuilt instead of stored.
+    #[serde(skip)]
+    index: Index,
+}
+
+/// Serialized form of a `DataSet`.
+#[derive(Deserialize)]
+struct StoredDataSet {
+    stats: Stats,
+    hash: u64,
+    payload: Payload,
+}
+
+impl From<StoredDataSet> for DataSet {
+    fn from(stored: StoredDataSet) -> Self {
+        Self {
+            index: Index::build(&stored.payload),
+            stats: stored.stats,
+            hash: stored.hash,
+            payload: stored.payload,
+        }
+    }
+}
+
+/// Lookup tables over the records, built once per data set so that requests
+/// never scan the record lists. Values are positions in the payload lists.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Index {
+    assets: HashMap<String, usize>,
+    exchanges: HashMap<String, usize>,
+    instruments_by_exchange: HashMap<String, Vec<usize>>,
+    instruments_by_asset: HashMap<String, Vec<usize>>,
+}
+
+impl Index {
+    fn build(payload: &Payload) -> Self {
+        let mut index = Self::default();
+        // On duplicate ids the first record wins.
+        for (i, asset) in payload.assets.iter().enumerate() {
+            index.assets.entry(asset.id().to_string()).or_insert(i);
+        }
+        for (i, exchange) in payload.exchanges.iter().enumerate() {
+            index
+                .exchanges
+                .entry(exchange.id().to_string())
+                .or_insert(i);
+        }
+        for (i, instrument) in payload.instruments.iter().enumerate() {
+            if let Some(exchange) = instrument.exchange_id() {
+                index
+                    .instruments_by_exchange
+                    .entry(exchange.to_string())
+                    .or_default()
+                    .push(i);
+            }
+            let base = instrument.base_asset();
+            let quote = instrument
+                .quote_asset()
+                .filter(|quote| Some(*quote) != base);
+            for asset in base.into_iter().chain(quote) {
+                index
+                    .instruments_by_asset
+                    .entry(asset.to_string())
+                    .or_default()
+                    .push(i);
+            }
+        }
+        index
+    }
 }
 
 impl DataSet {
+    /// Builds a data set from a freshly downloaded payload.
+    ///
+    /// The hash only covers the payload, not the download timestamp, so
+    /// re-downloading unchanged data yields the same hash.
+    pub fn from_payload(payload: Payload, downloaded_at: DateTime<Utc>) -> Result<Self, LoadError> {
+        let hash =
+            content_hash::content_hash(&payload).map_err(|e| LoadError::Hash(Arc::new(e)))?;
+        let stats = Stats::new(
+            downloaded_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            content_hash::to_hex(hash),
+            count(payload.assets.len()),
+            count(payload.exchanges.len()),
+            count(payload.instruments.len()),
+        );
+        Ok(Self {
+            stats,
+            hash,
+            index: Index::build(&payload),
+            payload,
+        })
+    }
     pub fn stats(&self) -> &Stats {
         &self.stats
     }
     pub fn hash(&self) -> u64 {
         self.hash
     }
+    pub fn assets(&self) -> &[Asset] {
+        &self.payload.assets
+    }
+    pub fn asset(&self, id: &str) -> Option<&Asset> {
+        self.index.assets.get(id).map(|i| &self.payload.assets[*i])
+    }
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.payload.exchanges
+    }
+    pub fn exchange(&self, id: &str) -> Option<&Exchange> {
+        self.index
+            .exchanges
+            .get(id)
+            .map(|i| &self.payload.exchanges[*i])
+    }
+
+    /// Instruments listed on `exchange` and trading `asset` as base or quote
+    /// asset, in payload order. A filter that is `None` matches everything.
+    pub fn instruments(&self, exchange: Option<&str>, asset: Option<&str>) -> Vec<&Instrument> {
+        let lookup = |index: &HashMap<String, Vec<usize>>, key: &str| -> Vec<usize> {
+            index.get(key).cloned().unwrap_or_default()
+        };
+        let positions = match (exchange, asset) {
+            (None, None) => return self.payload.instruments.iter().collect(),
+            (Some(exchange), None) => lookup(&self.index.instruments_by_exchange, exchange),
+            (None, Some(asset)) => lookup(&self.index.instruments_by_asset, asset),
+            (Some(exchange), Some(asset)) => intersect(
+                &lookup(&self.index.instruments_by_exchange, exchange),
+                &lookup(&self.index.instruments_by_asset, asset),
+            ),
+        };
+        positions
+            .into_iter()
+            .map(|i| &self.payload.instruments[i])
+            .collect()
+    }
+}
+
+/// Intersects two ascending position lists.
+fn intersect(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let (mut i, mut j) = (0, 0);
+    let mut both = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                both.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    both
+}
+
+fn count(len: usize) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn payload(json: &str) -> Payload {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn timestamp(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn from_payload_fills_stats() {
+        let payload = payload(
+            r#"{"assets": [{"id": "BTC"}, {"id": "USD"}], "exchanges": [{"id": "KRAKEN"}], "instruments": []}"#,
+        );
+
+        let data_set = DataSet::from_payload(payload, timestamp(1)).unwrap();
+
+        let expected = Stats::new(
+            "2024-05-01T01:00:00Z".to_string(),
+            content_hash::to_hex(data_set.hash()),
+            2,
+            1,
+            0,
+        );
+        assert_eq!(data_set.stats(), &expected);
+        assert_ne!(data_set.hash(), 0);
+    }
+
+    #[test]
+    fn hash_is_independent_of_field_order() {
+        let a = payload(
+            r#"{"assets": [{"id": "BTC", "name": "Bitcoin"}], "exchanges": [{"id": "KRAKEN", "country": "US"}]}"#,
+        );
+        let b = payload(
+            r#"{"exchanges": [{"country": "US", "id": "KRAKEN"}], "instruments": [], "assets": [{"name": "Bitcoin", "id": "BTC"}]}"#,
+        );
+
+        let a = DataSet::from_payload(a, timestamp(1)).unwrap();
+        let b = DataSet::from_payload(b, timestamp(1)).unwrap();
+
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(a.stats(), b.stats());
+    }
+
+    #[test]
+    fn hash_is_independent_of_download_time() {
+        let json = r#"{"assets": [{"id": "BTC"}]}"#;
+
+        let a = DataSet::from_payload(payload(json), timestamp(1)).unwrap();
+        let b = DataSet::from_payload(payload(json), timestamp(2)).unwrap();
+
+        assert_eq!(a.hash(), b.hash());
+    }
+
+    #[test]
+    fn hash_changes_with_content() {
+        let a =
+            DataSet::from_payload(payload(r#"{"assets": [{"id": "BTC"}]}"#), timestamp(1)).unwrap();
+        let b =
+            DataSet::from_payload(payload(r#"{"assets": [{"id": "ETH"}]}"#), timestamp(1)).unwrap();
+
+        assert_ne!(a.hash(), b.hash());
+    }
+
+    #[test]
+    fn hash_survives_serde_round_trip() {
+        let original = DataSet::from_payload(
+            payload(r#"{"assets": [{"id": "BTC", "tags": ["a", "b"]}], "exchanges": [{"id": "KRAKEN"}]}"#),
+            timestamp(1),
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&original).unwrap();
+        let restored: DataSet = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored, original);
+        assert_eq!(
+            content_hash::content_hash(&restored.payload).unwrap(),
+            original.hash()
+        );
+
+        let rebuilt = DataSet::from_payload(restored.payload, timestamp(1)).unwrap();
+        assert_eq!(rebuilt.hash(), original.hash());
+    }
+
+    #[test]
+    fn lookups_use_the_index() {
+        let data_set = DataSet::from_payload(
+            serde_json::from_slice(include_bytes!("../../data/sample.json")).unwrap(),
+            timestamp(1),
+        )
+        .unwrap();
+        let ids = |instruments: Vec<&Instrument>| -> Vec<String> {
+            instruments
+                .iter()
+                .map(|i| {
+                    serde_json::to_value(i).unwrap()["id"]
+                        .as_str()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(data_set.asset("BTC").map(Asset::id), Some("BTC"));
+        assert!(data_set.asset("DOGE").is_none());
+        assert_eq!(data_set.exchange("XETR").map(Exchange::id), Some("XETR"));
+        assert_eq!(data_set.instruments(None, None).len(), 6);
+        assert_eq!(
+            ids(data_set.instruments(Some("XNAS"), None)),
+            vec!["XNAS:AAPL", "XNAS:MSFT"]
+        );
+        assert_eq!(
+            ids(data_set.instruments(None, Some("EUR"))),
+            vec!["XETR:AAPL", "KRAKEN:BTC-EUR"]
+        );
+        assert_eq!(
+            ids(data_set.instruments(Some("KRAKEN"), Some("BTC"))),
+            vec!["KRAKEN:BTC-USD", "KRAKEN:BTC-EUR"]
+        );
+        assert!(data_set.instruments(Some("XNAS"), Some("BTC")).is_empty());
+        assert!(data_set.instruments(Some("NYSE"), None).is_empty());
+    }
+
+    #[test]
+    fn index_is_rebuilt_after_deserialization() {
+        let original = DataSet::from_payload(
+            payload(r#"{"instruments": [{"id": "KRAKEN:BTC-USD", "exchange_id": "KRAKEN", "base_asset": "BTC"}]}"#),
+            timestamp(1),
+        )
+        .unwrap();
+
+        let restored: DataSet =
+            serde_json::from_str(&serde_json::to_string(&original).unwrap()).unwrap();
+
+        assert_eq!(restored, original);
+        assert_eq!(restored.instruments(Some("KRAKEN"), Some("BTC")).len(), 1);
+    }
 }
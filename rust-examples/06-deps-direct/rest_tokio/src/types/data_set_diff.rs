@@ -0,0 +1,119 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// Changes between two data sets.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataSetDiff {
+    from: String,
+    to: String,
+    /// `Stats` fields whose value differs.
+    stats: Vec<FieldChange>,
+    assets: RecordDiff,
+    exchanges: RecordDiff,
+    instruments: RecordDiff,
+}
+
+impl DataSetDiff {
+    pub fn new(
+        from: String,
+        to: String,
+        stats: Vec<FieldChange>,
+        assets: RecordDiff,
+        exchanges: RecordDiff,
+        instruments: RecordDiff,
+    ) -> Self {
+        Self {
+            from,
+            to,
+            stats,
+            assets,
+            exchanges,
+            instruments,
+        }
+    }
+}
+
+/// One line per record kind, i.e. `assets 3 (+1 -0 ~2)`.
+impl Display for DataSetDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "assets {}, exchanges {}, instruments {}",
+            self.assets, self.exchanges, self.instruments
+        )
+    }
+}
+
+/// A `Stats` field and its value in both data sets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    field: String,
+    from: Value,
+    to: Value,
+}
+
+impl FieldChange {
+    pub fn new(field: String, from: Value, to: Value) -> Self {
+        Self { field, from, to }
+    }
+}
+
+/// Changes of one kind of record, identified by their ids.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordDiff {
+    count_from: usize,
+    count_to: usize,
+    count_delta: i64,
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+impl RecordDiff {
+    pub fn new(
+        count_from: usize,
+        count_to: usize,
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    ) -> Self {
+        Self {
+            count_from,
+            count_to,
+            count_delta: count_to as i64 - count_from as i64,
+            added,
+            removed,
+            modified,
+        }
+    }
+}
+
+impl Display for RecordDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (+{} -{} ~{})",
+            self.count_to,
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        )
+    }
+}
@@ -13,12 +13,23 @@
 // limitations under the License.
 
 
-use crate::types::data_set::DataSet;
-use arc_swap::ArcSwap;
-use std::sync::Arc;
+use crate::types::stats::Stats;
+use serde::{Deserialize, Serialize};
 
-pub(crate) mod health;
-pub(crate) mod data_set;
-pub(crate) mod stats;
+/// Entry of `GET /datasets`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataSetSummary {
+    name: String,
+    source: String,
+    stats: Stats,
+}
 
-pub(crate) type MetaDataStore = Arc<ArcSwap<DataSet>>;
+impl DataSetSummary {
+    pub fn new(name: String, source: String, stats: Stats) -> Self {
+        Self {
+            name,
+            source,
+            stats,
+        }
+    }
+}
@@ -13,29 +13,22 @@
 // limitations under the License.
 
 
-use std::error::Error;
-use std::fmt;
-use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
-pub struct InitError(pub String);
-
-impl From<&str> for InitError {
-    fn from(field0: &str) -> Self {
-        Self(field0.to_string())
-    }
+/// Query parameters of `GET /stats/diff`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffQuery {
+    /// Hash of the older data set; defaults to the one swapped in before the active one.
+    from: Option<String>,
+    /// Hash of the newer data set; defaults to the active one.
+    to: Option<String>,
 }
 
-impl From<String> for InitError {
-    fn from(field0: String) -> Self {
-        Self(field0)
+impl DiffQuery {
+    pub fn from(&self) -> Option<&str> {
+        self.from.as_deref()
     }
-}
-
-impl Display for InitError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "InitError: {}", self.0)
+    pub fn to(&self) -> Option<&str> {
+        self.to.as_deref()
     }
 }
-
-impl Error for InitError {}
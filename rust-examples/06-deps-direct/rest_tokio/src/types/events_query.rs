@@ -14,13 +14,16 @@
 
 
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
 
+/// Query parameters of `GET /events` and `GET /ws`.
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Stats {
-    download_timestamp: String,
-    hash: String,
-    number_assets: u32,
-    number_exchanges: u32,
-    number_instruments: u32,
+pub struct EventsQuery {
+    /// Same as the `Last-Event-ID` header, for clients that cannot set headers.
+    last_event_id: Option<u64>,
+}
+
+impl EventsQuery {
+    pub fn last_event_id(&self) -> Option<u64> {
+        self.last_event_id
+    }
 }
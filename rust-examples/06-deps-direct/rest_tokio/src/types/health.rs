@@ -13,17 +13,227 @@
 // limitations under the License.
 
 
+use crate::types::reload_status::ReloadStatus;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HealthStatus {
+    /// Alive, or ready to serve traffic.
+    Ok,
+    /// Alive, but should not receive traffic.
+    NotReady,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HealthReasonCode {
+    /// No data has been loaded successfully yet.
+    NoData,
+    /// The last successful load is older than the configured threshold, or
+    /// the data was restored from the disk cache because the live load failed.
+    Stale,
+}
+
+/// Why a probe is not `OK`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReason {
+    code: HealthReasonCode,
+    message: String,
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Health {
-    status: String,
+    status: HealthStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reasons: Vec<HealthReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_success: Option<String>,
 }
 
 impl Health {
     pub fn ok() -> Self {
         Self {
-            status: String::from("OK"),
+            status: HealthStatus::Ok,
+            reasons: Vec::new(),
+            last_success: None,
+        }
+    }
+
+    /// Readiness derived from the load history.
+    ///
+    /// The service is ready once data has been loaded and, if `max_staleness`
+    /// is set, as long as the last successful load is not older than that.
+    /// Data restored from the disk cache at startup counts as ready while its
+    /// download is within `max_staleness`, but is reported with a `STALE`
+    /// reason until the first live load succeeds.
+    pub fn readiness(
+        status: &ReloadStatus,
+        now: DateTime<Utc>,
+        max_staleness: Option<Duration>,
+    ) -> Self {
+        let mut reasons = Vec::new();
+        let mut notices = Vec::new();
+
+        match (status.last_success(), status.cached_download()) {
+            (None, Some(downloaded_at)) => {
+                notices.push(HealthReason {
+                    code: HealthReasonCode::Stale,
+                    message: match status.last_error() {
+                        Some(error) => format!(
+                            "Serving cached data downloaded at {}, live load failed: {}",
+                            downloaded_at, error
+                        ),
+                        None => format!("Serving cached data downloaded at {}", downloaded_at),
+                    },
+                });
+                if let Ok(downloaded_at) = DateTime::parse_from_rfc3339(downloaded_at) {
+                    let age = (now - downloaded_at.with_timezone(&Utc))
+                        .to_std()
+                        .unwrap_or_default();
+                    reasons.extend(too_old("Cached data downloaded", age, max_staleness));
+                }
+            }
+            (None, None) => reasons.push(HealthReason {
+                code: HealthReasonCode::NoData,
+                message: match (status.last_failure(), status.last_error()) {
+                    (Some(at), Some(error)) => format!(
+                        "No data loaded yet, last error at {}: {}",
+                        at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        error
+                    ),
+                    _ => String::from("No data loaded yet"),
+                },
+            }),
+            (Some(last_success), _) => {
+                let age = (now - last_success).to_std().unwrap_or_default();
+                reasons.extend(too_old("Last successful load", age, max_staleness));
+            }
         }
+
+        let status_code = if reasons.is_empty() {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::NotReady
+        };
+        reasons.append(&mut notices);
+
+        Self {
+            status: status_code,
+            reasons,
+            last_success: status
+                .last_success()
+                .map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        }
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        self.status
+    }
+}
+
+/// A `STALE` reason if `age` exceeds `max_staleness`.
+fn too_old(what: &str, age: Duration, max_staleness: Option<Duration>) -> Option<HealthReason> {
+    let max_staleness = max_staleness.filter(|max| age > *max)?;
+    Some(HealthReason {
+        code: HealthReasonCode::Stale,
+        message: format!(
+            "{} {}s ago exceeds the limit of {}s",
+            what,
+            age.as_secs(),
+            max_staleness.as_secs()
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap()
+    }
+
+    fn codes(health: &Health) -> Vec<HealthReasonCode> {
+        health.reasons.iter().map(|reason| reason.code).collect()
+    }
+
+    #[test]
+    fn not_ready_before_first_load() {
+        let mut status = ReloadStatus::default();
+        status.record_failure(at(1), String::from("connection refused"));
+
+        let health = Health::readiness(&status, at(2), Some(HOUR));
+
+        assert_eq!(health.status(), HealthStatus::NotReady);
+        assert_eq!(codes(&health), vec![HealthReasonCode::NoData]);
+        assert!(health.reasons[0].message.contains("connection refused"));
+    }
+
+    #[test]
+    fn ready_while_fresh_and_not_ready_when_stale() {
+        let mut status = ReloadStatus::default();
+        status.record_success(at(1));
+
+        let fresh = Health::readiness(&status, at(2), Some(2 * HOUR));
+        let stale = Health::readiness(&status, at(4), Some(2 * HOUR));
+        let unlimited = Health::readiness(&status, at(23), None);
+
+        assert_eq!(fresh.status(), HealthStatus::Ok);
+        assert!(fresh.reasons.is_empty());
+        assert_eq!(stale.status(), HealthStatus::NotReady);
+        assert_eq!(codes(&stale), vec![HealthReasonCode::Stale]);
+        assert_eq!(unlimited.status(), HealthStatus::Ok);
+    }
+
+    #[test]
+    fn ready_but_stale_on_cached_data() {
+        let mut status = ReloadStatus::default();
+        status.record_failure(at(1), String::from("connection refused"));
+        status.record_cache_fallback(String::from("2024-05-01T01:30:00Z"));
+
+        let cached = Health::readiness(&status, at(2), Some(HOUR));
+        status.record_success(at(3));
+        let live = Health::readiness(&status, at(3), Some(HOUR));
+
+        assert_eq!(cached.status(), HealthStatus::Ok);
+        assert_eq!(codes(&cached), vec![HealthReasonCode::Stale]);
+        assert!(cached.reasons[0].message.contains("2024-05-01T01:30:00Z"));
+        assert_eq!(live.status(), HealthStatus::Ok);
+        assert!(live.reasons.is_empty());
+    }
+
+    #[test]
+    fn not_ready_on_cached_data_older_than_max_staleness() {
+        let mut status = ReloadStatus::default();
+        status.record_cache_fallback(String::from("2024-05-01T01:00:00Z"));
+
+        let recent = Health::readiness(&status, at(2), Some(2 * HOUR));
+        let old = Health::readiness(&status, at(4), Some(2 * HOUR));
+
+        assert_eq!(recent.status(), HealthStatus::Ok);
+        assert_eq!(codes(&recent), vec![HealthReasonCode::Stale]);
+        assert_eq!(old.status(), HealthStatus::NotReady);
+        assert_eq!(
+            codes(&old),
+            vec![HealthReasonCode::Stale, HealthReasonCode::Stale]
+        );
+        assert!(old.reasons[0].message.contains("10800s ago"));
+    }
+
+    #[test]
+    fn serializes_status_codes() {
+        let json = serde_json::to_string(&Health::ok()).unwrap();
+        assert_eq!(json, r#"{"status":"OK"}"#);
+
+        let json = serde_json::to_string(&Health::readiness(&ReloadStatus::default(), at(1), None))
+            .unwrap();
+        assert!(json.contains(r#""status":"NOT_READY""#));
+        assert!(json.contains(r#""code":"NO_DATA""#));
     }
 }
@@ -0,0 +1,50 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// An asset pair traded on an exchange, i.e. BTC quoted in USD on Kraken.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Instrument {
+    id: String,
+    /// Ticker symbol on the exchange.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exchange_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_asset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quote_asset: Option<String>,
+    /// Fields the service does not model are passed through unchanged.
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Instrument {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn exchange_id(&self) -> Option<&str> {
+        self.exchange_id.as_deref()
+    }
+    pub fn base_asset(&self) -> Option<&str> {
+        self.base_asset.as_deref()
+    }
+    pub fn quote_asset(&self) -> Option<&str> {
+        self.quote_asset.as_deref()
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Filters of `GET /instruments`. Both filters must match if both are given.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentQuery {
+    /// Exchange id the instrument is listed on.
+    #[serde(default)]
+    exchange: Option<String>,
+    /// Asset id the instrument has as base or quote asset.
+    #[serde(default)]
+    asset: Option<String>,
+}
+
+impl InstrumentQuery {
+    pub fn exchange(&self) -> Option<&str> {
+        self.exchange.as_deref()
+    }
+    pub fn asset(&self) -> Option<&str> {
+        self.asset.as_deref()
+    }
+}
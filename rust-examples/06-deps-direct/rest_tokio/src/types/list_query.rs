@@ -0,0 +1,51 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Paging, sorting and projection parameters of the list routes.
+///
+/// The values are kept as strings and checked by `ListOptions::parse`, so that
+/// a malformed value is answered with an error body instead of a bare rejection.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Maximum number of records per page.
+    #[serde(default)]
+    limit: Option<String>,
+    /// `next_cursor` of the previous page.
+    #[serde(default)]
+    cursor: Option<String>,
+    /// Comma separated fields to sort by, descending if prefixed with `-`.
+    #[serde(default)]
+    sort: Option<String>,
+    /// Comma separated fields to include in each record.
+    #[serde(default)]
+    fields: Option<String>,
+}
+
+impl ListQuery {
+    pub fn limit(&self) -> Option<&str> {
+        self.limit.as_deref()
+    }
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+    pub fn sort(&self) -> Option<&str> {
+        self.sort.as_deref()
+    }
+    pub fn fields(&self) -> Option<&str> {
+        self.fields.as_deref()
+    }
+}
@@ -15,15 +15,14 @@
 
 use serde::{Deserialize, Serialize};
 
+/// Active log filter directives as reported by the admin API.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Health {
-    status: String,
+pub struct LogFilter {
+    filter: String,
 }
 
-impl Health {
-    pub fn ok() -> Self {
-        Self {
-            status: String::from("OK"),
-        }
+impl LogFilter {
+    pub fn new(filter: String) -> Self {
+        Self { filter }
     }
 }
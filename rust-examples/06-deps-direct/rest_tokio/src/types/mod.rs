@@ -13,12 +13,37 @@
 // limitations under the License.
 
 
-use crate::types::data_set::DataSet;
+use crate::types::active_data_set::ActiveDataSet;
 use arc_swap::ArcSwap;
 use std::sync::Arc;
 
-pub(crate) mod health;
+pub(crate) mod active_data_set;
+pub(crate) mod asset;
+pub(crate) mod build_info;
+pub(crate) mod content_hash;
+pub(crate) mod data_event;
 pub(crate) mod data_set;
+pub(crate) mod data_set_diff;
+pub(crate) mod data_set_summary;
+pub(crate) mod diff_query;
+pub(crate) mod events_query;
+pub(crate) mod exchange;
+pub(crate) mod health;
+pub(crate) mod instrument;
+pub(crate) mod instrument_query;
+pub(crate) mod list_query;
+pub(crate) mod log_filter;
+pub(crate) mod notification;
+pub(crate) mod page;
+pub(crate) mod payload;
+pub(crate) mod problem;
+pub(crate) mod quarantine;
+pub(crate) mod reload_outcome;
+pub(crate) mod reload_status;
+pub(crate) mod rollback_query;
+pub(crate) mod schedule_info;
+pub(crate) mod snapshot_history;
+pub(crate) mod snapshot_list;
 pub(crate) mod stats;
 
-pub(crate) type MetaDataStore = Arc<ArcSwap<DataSet>>;
+pub type MetaDataStore = Arc<ArcSwap<ActiveDataSet>>;
@@ -0,0 +1,59 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotificationEvent {
+    /// Reloads failed the configured number of times in a row.
+    ReloadFailing,
+    /// A reload succeeded after a `RELOAD_FAILING` notification.
+    ReloadRecovered,
+    /// The validation gate turned down a downloaded data set.
+    DatasetRejected,
+}
+
+/// JSON document sent to the webhook and passed to the notify command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    event: NotificationEvent,
+    source: String,
+    consecutive_failures: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    timestamp: String,
+}
+
+impl Notification {
+    pub fn new(
+        event: NotificationEvent,
+        source: String,
+        consecutive_failures: u32,
+        error: Option<String>,
+        timestamp: String,
+    ) -> Self {
+        Self {
+            event,
+            source,
+            consecutive_failures,
+            error,
+            timestamp,
+        }
+    }
+    pub fn event(&self) -> NotificationEvent {
+        self.event
+    }
+}
@@ -13,19 +13,21 @@
 // limitations under the License.
 
 
-use crate::types::health::Health;
-use crate::types::MetaDataStore;
-use warp;
+use crate::types::asset::Asset;
+use crate::types::exchange::Exchange;
+use crate::types::instrument::Instrument;
+use serde::{Deserialize, Serialize};
 
-pub(crate) async fn get_health_handler() -> Result<impl warp::Reply, warp::Rejection> {
-    let result = Health::ok();
-    Ok(warp::reply::json(&result))
-}
-
-pub(crate) async fn get_stats_handler(
-    store: MetaDataStore,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let guard = store.load();
-    let result = guard.stats();
-    Ok(warp::reply::json(result))
+/// Reference data as published by the upstream source.
+///
+/// Records carry an `id` and the fields the service indexes on. Any other
+/// fields are kept as they are, so they are served and hashed unchanged.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+    #[serde(default)]
+    pub exchanges: Vec<Exchange>,
+    #[serde(default)]
+    pub instruments: Vec<Instrument>,
 }
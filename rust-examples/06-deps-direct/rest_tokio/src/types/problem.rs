@@ -0,0 +1,94 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Stable, machine readable error codes of problem responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// No route or record at this path.
+    NotFound,
+    /// The route exists, but not for this method.
+    MethodNotAllowed,
+    /// Missing or invalid bearer token.
+    Unauthorized,
+    /// A query parameter is malformed.
+    InvalidQuery,
+    /// A header is missing or malformed.
+    InvalidHeader,
+    /// The request body is malformed.
+    InvalidBody,
+    /// The request body exceeds the limit of the route.
+    PayloadTooLarge,
+    /// The data set a cursor refers to is no longer kept.
+    CursorExpired,
+    /// A reload was requested while a snapshot is pinned.
+    SnapshotPinned,
+    /// The data source location cannot be used.
+    SourceLocation,
+    /// Reading the data source from disk failed.
+    SourceIo,
+    /// Downloading from the data source failed.
+    SourceHttp,
+    /// The data source answered with an error status.
+    SourceHttpStatus,
+    /// The data source did not answer in time.
+    SourceTimeout,
+    /// The data source delivered a document that does not decode.
+    PayloadDecode,
+    /// The data source delivered data that failed validation.
+    PayloadInvalid,
+    /// An unexpected error in the service.
+    Internal,
+}
+
+impl ErrorCode {
+    /// URI of the problem type, derived from the code.
+    pub fn type_uri(self) -> String {
+        let code = serde_json::to_value(self)
+            .ok()
+            .and_then(|code| code.as_str().map(str::to_string))
+            .unwrap_or_default();
+        format!(
+            "urn:rest-tokio:problem:{}",
+            code.to_ascii_lowercase().replace('_', "-")
+        )
+    }
+}
+
+/// Problem details of an error response, as of RFC 7807.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    code: ErrorCode,
+}
+
+impl Problem {
+    pub fn new(code: ErrorCode, status: u16, title: String, detail: Option<String>) -> Self {
+        Self {
+            problem_type: code.type_uri(),
+            title,
+            status,
+            detail,
+            code,
+        }
+    }
+}
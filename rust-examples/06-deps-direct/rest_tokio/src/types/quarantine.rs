@@ -0,0 +1,104 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::stats::Stats;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The last data sets turned down by the validation gate, newest first, as
+/// reported by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quarantine {
+    #[serde(skip)]
+    capacity: usize,
+    rejected: VecDeque<Rejection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rejection {
+    #[serde(flatten)]
+    stats: Stats,
+    rejected_at: String,
+    violations: Vec<String>,
+}
+
+impl Quarantine {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            rejected: VecDeque::new(),
+        }
+    }
+
+    /// Adds a rejection as the newest one and drops the oldest ones beyond the
+    /// capacity. Returns false if a data set with the same hash was already
+    /// quarantined; it moves to the front.
+    pub fn push(&mut self, rejection: Rejection) -> bool {
+        let before = self.rejected.len();
+        self.rejected
+            .retain(|known| known.stats.hash() != rejection.stats.hash());
+        let new = self.rejected.len() == before;
+        self.rejected.push_front(rejection);
+        self.rejected.truncate(self.capacity);
+        new
+    }
+}
+
+impl Rejection {
+    pub fn new(stats: Stats, rejected_at: String, violations: Vec<String>) -> Self {
+        Self {
+            stats,
+            rejected_at,
+            violations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(hash: &str, rejected_at: &str) -> Rejection {
+        Rejection::new(
+            Stats::new(String::new(), hash.to_string(), 0, 0, 0),
+            rejected_at.to_string(),
+            vec![String::from("assets: 0 records, expected at least 1")],
+        )
+    }
+
+    #[test]
+    fn keeps_the_newest_rejections_once() {
+        let mut quarantine = Quarantine::new(2);
+
+        assert!(quarantine.push(rejection("a", "1")));
+        assert!(quarantine.push(rejection("b", "2")));
+        assert!(!quarantine.push(rejection("a", "3")));
+        assert!(quarantine.push(rejection("c", "4")));
+
+        let json = serde_json::to_value(&quarantine).unwrap();
+        let rejected: Vec<(&str, &str)> = json["rejected"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["hash"].as_str().unwrap(),
+                    r["rejected_at"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(rejected, vec![("c", "4"), ("a", "3")]);
+    }
+}
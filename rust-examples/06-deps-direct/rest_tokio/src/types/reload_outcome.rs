@@ -0,0 +1,55 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Result of a data reload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReloadOutcome {
+    changed: bool,
+    old_hash: String,
+    new_hash: String,
+    /// True if the caller joined a reload that another caller started.
+    coalesced: bool,
+}
+
+impl ReloadOutcome {
+    pub fn new(changed: bool, old_hash: String, new_hash: String) -> Self {
+        Self {
+            changed,
+            old_hash,
+            new_hash,
+            coalesced: false,
+        }
+    }
+    pub fn into_coalesced(self) -> Self {
+        Self {
+            coalesced: true,
+            ..self
+        }
+    }
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+    pub fn old_hash(&self) -> &str {
+        &self.old_hash
+    }
+    pub fn new_hash(&self) -> &str {
+        &self.new_hash
+    }
+    pub fn coalesced(&self) -> bool {
+        self.coalesced
+    }
+}
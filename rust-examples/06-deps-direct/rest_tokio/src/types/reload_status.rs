@@ -0,0 +1,63 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Outcome of the data loads so far, as tracked by the reloader.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReloadStatus {
+    /// Time of the last load that downloaded and checked the data, changed or not.
+    last_success: Option<DateTime<Utc>>,
+    /// Time and error of the last failed load.
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    /// Failed loads since the last successful one.
+    consecutive_failures: u32,
+    /// Download time of the data restored from the disk cache, until the
+    /// first live load succeeds.
+    cached_download: Option<String>,
+}
+
+impl ReloadStatus {
+    pub fn last_success(&self) -> Option<DateTime<Utc>> {
+        self.last_success
+    }
+    pub fn last_failure(&self) -> Option<DateTime<Utc>> {
+        self.last_failure
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+    pub fn cached_download(&self) -> Option<&str> {
+        self.cached_download.as_deref()
+    }
+    pub fn record_success(&mut self, at: DateTime<Utc>) {
+        self.last_success = Some(at);
+        self.consecutive_failures = 0;
+        self.cached_download = None;
+    }
+    pub fn record_cache_fallback(&mut self, download_timestamp: String) {
+        self.cached_download = Some(download_timestamp);
+    }
+    pub fn record_failure(&mut self, at: DateTime<Utc>, error: String) {
+        self.last_failure = Some(at);
+        self.last_error = Some(error);
+        self.consecutive_failures += 1;
+    }
+}
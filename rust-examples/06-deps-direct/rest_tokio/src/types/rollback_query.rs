@@ -13,20 +13,18 @@
 // limitations under the License.
 
 
-use crate::types::stats::Stats;
 use serde::{Deserialize, Serialize};
 
+/// Query parameters of `POST /admin/rollback/{hash}`.
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
-pub struct DataSet {
-    stats: Stats,
-    hash: u64,
+pub struct RollbackQuery {
+    /// Also pin the snapshot, which suspends reloads until it is unpinned.
+    #[serde(default)]
+    pin: bool,
 }
 
-impl DataSet {
-    pub fn stats(&self) -> &Stats {
-        &self.stats
-    }
-    pub fn hash(&self) -> u64 {
-        self.hash
+impl RollbackQuery {
+    pub fn pin(&self) -> bool {
+        self.pin
     }
 }
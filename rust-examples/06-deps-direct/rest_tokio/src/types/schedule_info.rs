@@ -0,0 +1,41 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use serde::{Deserialize, Serialize};
+
+/// Reload schedule and its next planned runs as reported by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleInfo {
+    schedule: String,
+    timezone: String,
+    jitter_secs: u64,
+    next_runs: Vec<String>,
+}
+
+impl ScheduleInfo {
+    pub fn new(
+        schedule: String,
+        timezone: String,
+        jitter_secs: u64,
+        next_runs: Vec<String>,
+    ) -> Self {
+        Self {
+            schedule,
+            timezone,
+            jitter_secs,
+            next_runs,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::data_set::DataSet;
+use crate::types::snapshot_list::{SnapshotInfo, SnapshotList};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// The last data sets swapped into the store, newest first, and the
+/// snapshot pinned by an operator, if any.
+#[derive(Debug)]
+pub struct SnapshotHistory {
+    capacity: usize,
+    snapshots: VecDeque<Arc<DataSet>>,
+    pinned: Option<String>,
+}
+
+impl SnapshotHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            snapshots: VecDeque::new(),
+            pinned: None,
+        }
+    }
+
+    /// Adds a data set as the newest snapshot and drops the oldest ones
+    /// beyond the capacity. A snapshot with the same hash moves to the front.
+    pub fn push(&mut self, data_set: Arc<DataSet>) {
+        self.snapshots
+            .retain(|snapshot| snapshot.hash() != data_set.hash());
+        self.snapshots.push_front(data_set);
+        self.snapshots.truncate(self.capacity);
+    }
+
+    /// Takes over the snapshots of `other` as if they had been pushed here,
+    /// oldest first. Its pin is not taken over.
+    pub fn extend_from(&mut self, other: &SnapshotHistory) {
+        for snapshot in other.snapshots.iter().rev() {
+            self.push(snapshot.clone());
+        }
+    }
+
+    /// Looks up a snapshot by its hex hash.
+    pub fn find(&self, hash: &str) -> Option<Arc<DataSet>> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.stats().hash() == hash)
+            .cloned()
+    }
+
+    /// The snapshot swapped in before the one with `hash`.
+    pub fn previous(&self, hash: &str) -> Option<Arc<DataSet>> {
+        self.snapshots
+            .iter()
+            .skip_while(|snapshot| snapshot.stats().hash() != hash)
+            .nth(1)
+            .cloned()
+    }
+
+    pub fn pinned(&self) -> Option<&str> {
+        self.pinned.as_deref()
+    }
+    pub fn pin(&mut self, hash: &str) {
+        self.pinned = Some(hash.to_string());
+    }
+    pub fn unpin(&mut self) -> Option<String> {
+        self.pinned.take()
+    }
+
+    /// Lists the snapshots, marking the one with the `active` hash.
+    pub fn list(&self, active: &str) -> SnapshotList {
+        let snapshots = self
+            .snapshots
+            .iter()
+            .map(|snapshot| {
+                SnapshotInfo::new(snapshot.stats().clone(), snapshot.stats().hash() == active)
+            })
+            .collect();
+        SnapshotList::new(self.pinned.clone(), snapshots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::payload::Payload;
+    use chrono::Utc;
+
+    fn data_set(id: &str) -> Arc<DataSet> {
+        let payload: Payload =
+            serde_json::from_str(&format!(r#"{{"assets": [{{"id": "{}"}}]}}"#, id)).unwrap();
+        Arc::new(DataSet::from_payload(payload, Utc::now()).unwrap())
+    }
+
+    fn info(data_set: &DataSet, active: bool) -> SnapshotInfo {
+        SnapshotInfo::new(data_set.stats().clone(), active)
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshots() {
+        let (a, b, c) = (data_set("A"), data_set("B"), data_set("C"));
+        let mut history = SnapshotHistory::new(2);
+
+        history.push(a.clone());
+        history.push(b.clone());
+        history.push(c.clone());
+
+        assert_eq!(
+            history.list(c.stats().hash()),
+            SnapshotList::new(None, vec![info(&c, true), info(&b, false)])
+        );
+        assert!(history.find(a.stats().hash()).is_none());
+        assert_eq!(history.find(b.stats().hash()), Some(b));
+    }
+
+    #[test]
+    fn reloaded_snapshot_moves_to_the_front() {
+        let (a, b) = (data_set("A"), data_set("B"));
+        let mut history = SnapshotHistory::new(3);
+
+        history.push(a.clone());
+        history.push(b.clone());
+        history.push(data_set("A"));
+        history.pin(b.stats().hash());
+
+        assert_eq!(
+            history.list(a.stats().hash()),
+            SnapshotList::new(
+                Some(b.stats().hash().to_string()),
+                vec![info(&a, true), info(&b, false)]
+            )
+        );
+    }
+
+    #[test]
+    fn previous_is_the_snapshot_swapped_in_before() {
+        let (a, b) = (data_set("A"), data_set("B"));
+        let mut history = SnapshotHistory::new(3);
+
+        history.push(a.clone());
+        history.push(b.clone());
+
+        assert_eq!(history.previous(b.stats().hash()), Some(a.clone()));
+        assert!(history.previous(a.stats().hash()).is_none());
+        assert!(history.previous("unknown").is_none());
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::types::stats::Stats;
+use serde::{Deserialize, Serialize};
+
+/// Snapshots kept for rollback as reported by the admin API, newest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotList {
+    /// Hash of the pinned snapshot. Reloads are suspended while one is pinned.
+    pinned: Option<String>,
+    snapshots: Vec<SnapshotInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    #[serde(flatten)]
+    stats: Stats,
+    /// True for the snapshot currently served.
+    active: bool,
+}
+
+impl SnapshotList {
+    pub fn new(pinned: Option<String>, snapshots: Vec<SnapshotInfo>) -> Self {
+        Self { pinned, snapshots }
+    }
+}
+
+impl SnapshotInfo {
+    pub fn new(stats: Stats, active: bool) -> Self {
+        Self { stats, active }
+    }
+}
@@ -24,3 +24,36 @@ pub struct Stats {
     number_exchanges: u32,
     number_instruments: u32,
 }
+
+impl Stats {
+    pub fn new(
+        download_timestamp: String,
+        hash: String,
+        number_assets: u32,
+        number_exchanges: u32,
+        number_instruments: u32,
+    ) -> Self {
+        Self {
+            download_timestamp,
+            hash,
+            number_assets,
+            number_exchanges,
+            number_instruments,
+        }
+    }
+    pub fn download_timestamp(&self) -> &str {
+        &self.download_timestamp
+    }
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+    pub fn number_assets(&self) -> u32 {
+        self.number_assets
+    }
+    pub fn number_exchanges(&self) -> u32 {
+        self.number_exchanges
+    }
+    pub fn number_instruments(&self) -> u32 {
+        self.number_instruments
+    }
+}
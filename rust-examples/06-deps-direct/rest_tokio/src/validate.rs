@@ -0,0 +1,377 @@
+// Copyright 2024 The Bazel examples and tutorials Authors & Contributors. // All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::config::ValidationConfig;
+use crate::types::data_set::DataSet;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A rule of the validation gate that a data set breaks. `kind` names the
+/// record list: `assets`, `exchanges` or `instruments`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Records without an id.
+    MissingIds { kind: &'static str, count: usize },
+    /// Records whose id an earlier record already has, with the first of them.
+    DuplicateIds {
+        kind: &'static str,
+        count: usize,
+        example: String,
+    },
+    /// Fewer records than configured as the minimum.
+    TooFewRecords {
+        kind: &'static str,
+        count: u32,
+        min: u32,
+    },
+    /// The record count dropped further than allowed against the served data.
+    CountDropped {
+        kind: &'static str,
+        from: u32,
+        to: u32,
+        percent: u64,
+        max_percent: u32,
+    },
+    /// Instruments listed on exchanges the data set does not contain, with
+    /// the first of them.
+    UnknownExchanges {
+        count: usize,
+        instrument: String,
+        exchange: String,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingIds { kind, count } => write!(f, "{}: {} records without id", kind, count),
+            Self::DuplicateIds {
+                kind,
+                count,
+                example,
+            } => write!(f, "{}: {} duplicate ids, i.e. {}", kind, count, example),
+            Self::TooFewRecords { kind, count, min } => {
+                write!(f, "{}: {} records, expected at least {}", kind, count, min)
+            }
+            Self::CountDropped {
+                kind,
+                from,
+                to,
+                percent,
+                max_percent,
+            } => write!(
+                f,
+                "{}: dropped from {} to {} records ({}%), allowed are {}%",
+                kind, from, to, percent, max_percent
+            ),
+            Self::UnknownExchanges {
+                count,
+                instrument,
+                exchange,
+            } => write!(
+                f,
+                "instruments: {} listed on unknown exchanges, i.e. {} on {}",
+                count, instrument, exchange
+            ),
+        }
+    }
+}
+
+/// Checks a downloaded data set before it is swapped in.
+///
+/// Every record needs a unique, non-empty id. Depending on the config, a data
+/// set is also rejected if it has too few records, if a record count dropped
+/// too far against the served data set, or if instruments are listed on
+/// exchanges it does not contain. The default validator only checks the ids.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Validator {
+    min_assets: u32,
+    min_exchanges: u32,
+    min_instruments: u32,
+    /// Largest drop of a count in percent; `None` disables the check.
+    max_drop_percent: Option<u32>,
+    referential_integrity: bool,
+    quarantine_size: usize,
+}
+
+impl Validator {
+    pub(crate) fn from_config(config: &ValidationConfig) -> Self {
+        Self {
+            min_assets: config.min_assets,
+            min_exchanges: config.min_exchanges,
+            min_instruments: config.min_instruments,
+            max_drop_percent: (config.max_drop_percent < 100).then_some(config.max_drop_percent),
+            referential_integrity: config.referential_integrity,
+            quarantine_size: config.quarantine_size,
+        }
+    }
+
+    /// Number of rejected data sets kept for inspection.
+    pub(crate) fn quarantine_size(&self) -> usize {
+        self.quarantine_size
+    }
+
+    /// Checks `candidate` against the `current` data set. Returns all
+    /// violations at once, or none if the data set can be swapped in.
+    pub(crate) fn validate(&self, current: &DataSet, candidate: &DataSet) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        check_ids(
+            "assets",
+            candidate.assets().iter().map(|a| a.id()),
+            &mut violations,
+        );
+        check_ids(
+            "exchanges",
+            candidate.exchanges().iter().map(|e| e.id()),
+            &mut violations,
+        );
+        check_ids(
+            "instruments",
+            candidate
+                .instruments(None, None)
+                .into_iter()
+                .map(|i| i.id()),
+            &mut violations,
+        );
+
+        let counts = [
+            (
+                "assets",
+                self.min_assets,
+                current.stats().number_assets(),
+                candidate.stats().number_assets(),
+            ),
+            (
+                "exchanges",
+                self.min_exchanges,
+                current.stats().number_exchanges(),
+                candidate.stats().number_exchanges(),
+            ),
+            (
+                "instruments",
+                self.min_instruments,
+                current.stats().number_instruments(),
+                candidate.stats().number_instruments(),
+            ),
+        ];
+        for (kind, min, from, to) in counts {
+            if to < min {
+                violations.push(Violation::TooFewRecords {
+                    kind,
+                    count: to,
+                    min,
+                });
+            }
+            if let Some(max_drop) = self.max_drop_percent {
+                let drop = drop_percent(from, to);
+                if drop > u64::from(max_drop) {
+                    violations.push(Violation::CountDropped {
+                        kind,
+                        from,
+                        to,
+                        percent: drop,
+                        max_percent: max_drop,
+                    });
+                }
+            }
+        }
+
+        if self.referential_integrity {
+            let unknown: Vec<(&str, &str)> = candidate
+                .instruments(None, None)
+                .into_iter()
+                .filter_map(|instrument| {
+                    let exchange = instrument.exchange_id()?;
+                    candidate
+                        .exchange(exchange)
+                        .is_none()
+                        .then_some((instrument.id(), exchange))
+                })
+                .collect();
+            if let Some((instrument, exchange)) = unknown.first() {
+                violations.push(Violation::UnknownExchanges {
+                    count: unknown.len(),
+                    instrument: instrument.to_string(),
+                    exchange: exchange.to_string(),
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+/// Reports empty and duplicate ids, with the first duplicate as an example.
+fn check_ids<'a>(
+    kind: &'static str,
+    ids: impl Iterator<Item = &'a str>,
+    violations: &mut Vec<Violation>,
+) {
+    let mut seen = HashSet::new();
+    let mut empty = 0;
+    let mut duplicates = Vec::new();
+    for id in ids {
+        if id.trim().is_empty() {
+            empty += 1;
+        } else if !seen.insert(id) {
+            duplicates.push(id);
+        }
+    }
+    if empty > 0 {
+        violations.push(Violation::MissingIds { kind, count: empty });
+    }
+    if let Some(example) = duplicates.first() {
+        violations.push(Violation::DuplicateIds {
+            kind,
+            count: duplicates.len(),
+            example: example.to_string(),
+        });
+    }
+}
+
+/// Share of `from` records missing in `to`, rounded down.
+fn drop_percent(from: u32, to: u32) -> u64 {
+    if from == 0 || to >= from {
+        return 0;
+    }
+    u64::from(from - to) * 100 / u64::from(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn data_set(json: &str) -> DataSet {
+        DataSet::from_payload(serde_json::from_str(json).unwrap(), Utc::now()).unwrap()
+    }
+
+    fn messages(violations: Vec<Violation>) -> Vec<String> {
+        violations.iter().map(ToString::to_string).collect()
+    }
+
+    fn sample() -> DataSet {
+        DataSet::from_payload(
+            serde_json::from_slice(include_bytes!("../data/sample.json")).unwrap(),
+            Utc::now(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn default_config_accepts_the_sample_data() {
+        let validator = Validator::from_config(&ValidationConfig::default());
+
+        assert!(
+            validator
+                .validate(&DataSet::default(), &sample())
+                .is_empty()
+        );
+        assert!(validator.validate(&sample(), &sample()).is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_and_duplicate_ids() {
+        let violations = Validator::default().validate(
+            &DataSet::default(),
+            &data_set(r#"{"assets": [{"id": "BTC"}, {"id": " "}, {"id": "BTC"}], "exchanges": [{"id": "KRAKEN"}]}"#),
+        );
+
+        assert_eq!(
+            violations,
+            vec![
+                Violation::MissingIds {
+                    kind: "assets",
+                    count: 1
+                },
+                Violation::DuplicateIds {
+                    kind: "assets",
+                    count: 1,
+                    example: String::from("BTC")
+                },
+            ]
+        );
+        assert_eq!(
+            messages(violations),
+            vec![
+                "assets: 1 records without id",
+                "assets: 1 duplicate ids, i.e. BTC"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_too_few_records_and_large_drops() {
+        let validator = Validator::from_config(&ValidationConfig {
+            min_exchanges: 1,
+            max_drop_percent: 40,
+            ..ValidationConfig::default()
+        });
+        let current = data_set(r#"{"assets": [{"id": "BTC"}, {"id": "ETH"}, {"id": "USD"}]}"#);
+
+        let shrunk = data_set(r#"{"assets": [{"id": "BTC"}], "exchanges": [{"id": "KRAKEN"}]}"#);
+        assert_eq!(
+            messages(validator.validate(&current, &shrunk)),
+            vec!["assets: dropped from 3 to 1 records (66%), allowed are 40%"]
+        );
+
+        let empty = data_set("{}");
+        assert_eq!(
+            messages(validator.validate(&current, &empty)),
+            vec![
+                "assets: dropped from 3 to 0 records (100%), allowed are 40%",
+                "exchanges: 0 records, expected at least 1",
+            ]
+        );
+
+        // Without data served yet, only the minimum counts apply.
+        assert!(validator.validate(&DataSet::default(), &shrunk).is_empty());
+    }
+
+    #[test]
+    fn max_drop_of_100_percent_disables_the_check() {
+        let validator = Validator::from_config(&ValidationConfig {
+            max_drop_percent: 100,
+            ..ValidationConfig::default()
+        });
+
+        assert!(validator.validate(&sample(), &data_set("{}")).is_empty());
+    }
+
+    #[test]
+    fn rejects_instruments_on_unknown_exchanges() {
+        let candidate = data_set(
+            r#"{"exchanges": [{"id": "KRAKEN"}], "instruments": [
+                {"id": "KRAKEN:BTC-USD", "exchange_id": "KRAKEN"},
+                {"id": "XNAS:AAPL", "exchange_id": "XNAS"},
+                {"id": "XNAS:MSFT", "exchange_id": "XNAS"},
+                {"id": "OTC:BTC-EUR"}
+            ]}"#,
+        );
+
+        let strict = Validator::from_config(&ValidationConfig::default());
+        assert_eq!(
+            messages(strict.validate(&DataSet::default(), &candidate)),
+            vec!["instruments: 2 listed on unknown exchanges, i.e. XNAS:AAPL on XNAS"]
+        );
+        assert!(
+            Validator::default()
+                .validate(&DataSet::default(), &candidate)
+                .is_empty()
+        );
+    }
+}
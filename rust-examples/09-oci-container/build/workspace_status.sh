@@ -0,0 +1,7 @@
+#!/usr/bin/env bash
+# Workspace status of stamped builds, i.e. `bazel build --stamp //...`.
+# Keys prefixed with STABLE_ rebuild the stamped targets when they change.
+# https://bazel.build/docs/user-manual#workspace-status-command
+set -euo pipefail
+
+echo "STABLE_GIT_COMMIT $(git rev-parse HEAD 2>/dev/null || echo unknown)"